@@ -1,9 +1,70 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, Address, Env, Vec, Bytes,
+    auth::{Context, CustomAccountInterface},
+    contract, contracterror, contractevent, contractimpl, contracttype,
+    crypto::Hash,
+    token, vec,
+    xdr::ToXdr,
+    Address, Bytes, BytesN, Env, Map, MuxedAddress, Symbol, TryFromVal, Val, Vec,
 };
 
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum Error {
+    AlreadyInitialized = 1,
+    NotInitialized = 2,
+    InvalidThreshold = 3,
+    NoSigners = 4,
+    NotAdmin = 5,
+    NotSigner = 6,
+    SignerAlreadyExists = 7,
+    SignerNotFound = 8,
+    BelowThreshold = 9,
+    TransactionNotFound = 10,
+    NotPending = 11,
+    InvalidAmount = 12,
+    SelfInvocation = 13,
+    NoOperations = 14,
+    TooManyOperations = 15,
+    AlreadyRejected = 16,
+    NotProposerOrAdmin = 17,
+    TransactionExpired = 18,
+    NotExpired = 19,
+    NotQueued = 20,
+    TimelockNotElapsed = 21,
+    InsufficientApprovals = 22,
+    NotApproved = 23,
+    AdminDisabled = 24,
+    InvalidWeight = 25,
+    GroupNotFound = 26,
+    InvalidQuorum = 27,
+    GroupInUse = 28,
+    InvalidTiers = 29,
+    InvalidSpendingLimit = 30,
+    SpendingLimitExceeded = 31,
+    LimitedTokenInvocation = 32,
+    InvalidAllowance = 33,
+    AllowanceNotFound = 34,
+    AllowanceExpired = 35,
+    AllowanceExceeded = 36,
+    RecipientNotAllowed = 37,
+    RecipientDenied = 38,
+    NotGuardian = 39,
+    InvalidGuardians = 40,
+    RecoveryInProgress = 41,
+    RecoveryNotFound = 42,
+    RecoveryNotReady = 43,
+    ContractPaused = 44,
+    NotPaused = 45,
+    UnknownSchemaVersion = 48,
+    SignerKeyNotFound = 49,
+    SignatureReplayed = 51,
+    ExecutionDelayActive = 52,
+    InvalidSignature = 53,
+}
+
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
@@ -11,9 +72,50 @@ pub enum DataKey {
     Threshold,
     Signers,
     NextId,
+    ProposalLifetime,
+    ExecutionDelay,
     Transaction(u64),
     Approvals(u64),
+    Rejections(u64),
+    /// Per signer, how often they have approved, rejected or revoked on a
+    /// transaction. Approval signatures carry the count, so each is spent
+    /// by the vote it casts and stale ones fail.
+    VoteNonces(u64),
     Signer(Address),
+    Groups,
+    Group(Symbol),
+    GroupQuorums,
+    ApprovalTiers,
+    SpendingLimit(Address),
+    SpendingHistory(Address),
+    /// Spending accumulator of schema versions before 8, replaced by
+    /// `SpendingHistory`.
+    SpendingWindow(Address),
+    Allowance(Address, Address),
+    /// Tokens a signer holds an allowance of.
+    AllowanceTokens(Address),
+    AllowlistEnabled,
+    AllowedRecipient(Address),
+    DeniedRecipient(Address),
+    Guardians,
+    Recovery,
+    Paused,
+    PauseThreshold,
+    PauseVotes,
+    UnpauseVotes,
+    SchemaVersion,
+    /// Next transaction id an interrupted migration step resumes from.
+    MigrationCursor,
+    /// Ascending ids of the transactions currently in an open status,
+    /// `Pending` or `Queued`.
+    StatusIndex(TransactionStatus),
+    /// Ascending ids of the pending transactions a signer has not voted on.
+    Inbox(Address),
+    /// Key a signer approves with off-chain.
+    SignerKey(Address),
+    /// Marks the hash of a payload `execute_with_signatures` has run, or a
+    /// signer revoked before it could.
+    ExecutedPayload(BytesN<32>),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -23,18 +125,546 @@ pub enum TransactionStatus {
     Executed,
     Rejected,
     Cancelled,
+    Expired,
+    Queued,
 }
 
-#[derive(Clone)]
+/// Payment of `amount` of `token` from the vault to `to`.
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[contracttype]
-pub struct Transaction {
-    pub id: u64,
+pub struct Transfer {
+    pub token: Address,
     pub to: Address,
     pub amount: i128,
+}
+
+/// Call of `function` on `contract` with `args`, made by the vault itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct Invocation {
+    pub contract: Address,
+    pub function: Symbol,
+    pub args: Vec<Val>,
+}
+
+/// Requires at least `min_approvals` approvals from members of `group`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct GroupQuorum {
+    pub group: Symbol,
+    pub min_approvals: u32,
+}
+
+/// Proposals moving at most `max_amount` of a single token need only
+/// `threshold` approval weight instead of the vault's global threshold.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct ApprovalTier {
+    pub max_amount: i128,
+    pub threshold: u32,
+}
+
+/// At most `amount` of a token may leave the vault per `window` seconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct SpendingLimit {
+    pub amount: i128,
+    pub window: u64,
+}
+
+/// Amounts of a token spent recently, keyed by sub-window: the ledger
+/// timestamp divided by the limit's window over `SPENDING_BUCKETS`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct SpendingHistory {
+    pub buckets: Map<u64, i128>,
+}
+
+/// Amount of a token spent since `start`, the beginning of the current
+/// spending window, as kept before schema version 8.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+struct SpendingWindow {
+    start: u64,
+    spent: i128,
+}
+
+/// Amount of a token a signer may still send from the vault on their own,
+/// until the ledger timestamp `expires_at`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct Allowance {
+    pub amount: i128,
+    pub expires_at: u64,
+}
+
+/// Guardians able to replace the signer set once `threshold` of them agree
+/// and `delay` seconds pass without a signer vetoing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct GuardianConfig {
+    pub guardians: Vec<Address>,
+    pub threshold: u32,
+    pub delay: u64,
+}
+
+/// A proposed replacement of the signer set. `ready_at` is set once enough
+/// guardians have approved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct Recovery {
+    pub signers: Vec<Address>,
+    pub threshold: u32,
+    pub approvals: Vec<Address>,
+    pub ready_at: Option<u64>,
+}
+
+/// Public key a signer signs with instead of authorizing as its `Address`:
+/// an ed25519 key, or the secp256r1 key of a WebAuthn passkey in
+/// uncompressed SEC1 form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub enum SignerKey {
+    Ed25519(BytesN<32>),
+    Secp256r1(BytesN<65>),
+}
+
+/// A signature over a 32-byte digest by a `SignerKey` of the same kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub enum SignerSignature {
+    Ed25519(BytesN<64>),
+    Secp256r1(WebAuthnSignature),
+}
+
+/// A WebAuthn assertion. The passkey signs `authenticator_data` followed by
+/// the SHA-256 of `client_data_json`, whose challenge must be the digest,
+/// base64url-encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct WebAuthnSignature {
+    pub authenticator_data: Bytes,
+    pub client_data_json: Bytes,
+    pub signature: BytesN<64>,
+}
+
+/// A proposal as signed off-chain for `execute_with_signatures`. Each
+/// payload runs at most once, and not after the ledger timestamp
+/// `valid_until`; signers pick a fresh `nonce` to repeat an otherwise
+/// identical proposal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct ProposalPayload {
+    pub nonce: u64,
+    pub valid_until: u64,
+    pub operations: Vec<Operation>,
+    pub data: Bytes,
+}
+
+/// An on-chain proposal as signed off-chain for `approve_with_signature`.
+/// `nonce` is the number of votes the signer has cast on it so far.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct ApprovalPayload {
+    pub tx_id: u64,
+    pub nonce: u32,
+    pub operations: Vec<Operation>,
+    pub data: Bytes,
+}
+
+/// An action the vault performs once a proposal is approved. Besides moving
+/// funds and calling other contracts, the vault governs its own signer set,
+/// threshold and admin through these.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub enum Operation {
+    Transfer(Transfer),
+    Invoke(Invocation),
+    AddSigner(Address, u32),
+    RemoveSigner(Address),
+    SetSignerWeight(Address, u32),
+    UpdateThreshold(u32),
+    SetAdmin(Option<Address>),
+    SetGroup(Symbol, Vec<Address>),
+    SetGroupQuorums(Vec<GroupQuorum>),
+    SetApprovalTiers(Vec<ApprovalTier>),
+    SetSpendingLimit(Address, SpendingLimit),
+    RemoveSpendingLimit(Address),
+    /// Grants `signer` an allowance of `token`, replacing any previous one.
+    GrantAllowance(Address, Address, Allowance),
+    RevokeAllowance(Address, Address),
+    /// When enabled, transfers may only go to allowlisted recipients.
+    SetAllowlistEnabled(bool),
+    SetRecipientAllowed(Address, bool),
+    SetRecipientDenied(Address, bool),
+    SetGuardians(GuardianConfig),
+    /// Number of signers or guardians whose votes pause the vault.
+    SetPauseThreshold(u32),
+    /// Replaces the contract's code with the uploaded Wasm of this hash.
+    /// The new code takes over after the executing call; run `migrate`
+    /// afterwards to bring storage up to its schema.
+    Upgrade(BytesN<32>),
+}
+
+/// Upper bound on the operations in one proposal, keeping execution within
+/// a single transaction's resource limits.
+pub const MAX_OPERATIONS: u32 = 64;
+
+/// Version of the storage layout this code reads and writes.
+pub const SCHEMA_VERSION: u32 = 9;
+
+/// Shortest recovery delay guardians may be given, leaving signers time to
+/// notice and veto a recovery.
+pub const MIN_RECOVERY_DELAY: u64 = 86_400;
+
+/// Number of sub-windows a spending limit's window is divided into. A spend
+/// counts against the limit for at least one full window after it happens.
+pub const SPENDING_BUCKETS: u64 = 24;
+
+/// Most transactions `list_transactions` returns in one call.
+pub const MAX_PAGE_SIZE: u32 = 50;
+
+/// Longest WebAuthn client data accepted.
+const MAX_CLIENT_DATA_LEN: usize = 1024;
+
+/// Most transactions one `migrate` call visits, so that vaults with a long
+/// history migrate over several calls.
+pub const MIGRATION_BATCH: u64 = 25;
+
+const DAY_IN_LEDGERS: u32 = 17_280;
+
+/// Number of ledgers the vault's entries are kept alive for after a touch.
+pub const TTL_EXTEND_TO: u32 = 30 * DAY_IN_LEDGERS;
+
+/// Entries are only extended once their remaining TTL drops below this.
+pub const TTL_THRESHOLD: u32 = TTL_EXTEND_TO - DAY_IN_LEDGERS;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct Transaction {
+    pub id: u64,
+    pub operations: Vec<Operation>,
     pub data: Bytes,
     pub status: TransactionStatus,
     pub proposed_by: Address,
     pub created_at: u64,
+    /// Ledger timestamp from which the proposal can no longer be approved.
+    pub expires_at: Option<u64>,
+    /// Ledger timestamp from which a queued proposal may be executed.
+    pub eta: Option<u64>,
+}
+
+// --- Events ---
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Initialized {
+    pub admin: Option<Address>,
+    pub signers: Vec<Address>,
+    pub threshold: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignerAdded {
+    #[topic]
+    pub signer: Address,
+    pub weight: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignerRemoved {
+    #[topic]
+    pub signer: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignerWeightUpdated {
+    #[topic]
+    pub signer: Address,
+    pub weight: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThresholdUpdated {
+    pub threshold: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupUpdated {
+    #[topic]
+    pub group: Symbol,
+    pub members: Vec<Address>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupQuorumsUpdated {
+    pub quorums: Vec<GroupQuorum>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApprovalTiersUpdated {
+    pub tiers: Vec<ApprovalTier>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpendingLimitUpdated {
+    #[topic]
+    pub token: Address,
+    pub limit: Option<SpendingLimit>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllowanceGranted {
+    #[topic]
+    pub signer: Address,
+    #[topic]
+    pub token: Address,
+    pub amount: i128,
+    pub expires_at: u64,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllowanceRevoked {
+    #[topic]
+    pub signer: Address,
+    #[topic]
+    pub token: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllowanceSpent {
+    #[topic]
+    pub signer: Address,
+    #[topic]
+    pub token: Address,
+    pub to: Address,
+    pub amount: i128,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllowlistToggled {
+    pub enabled: bool,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecipientAllowed {
+    #[topic]
+    pub recipient: Address,
+    pub allowed: bool,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecipientDenied {
+    #[topic]
+    pub recipient: Address,
+    pub denied: bool,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuardiansUpdated {
+    pub guardians: Vec<Address>,
+    pub threshold: u32,
+    pub delay: u64,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryInitiated {
+    #[topic]
+    pub guardian: Address,
+    pub signers: Vec<Address>,
+    pub threshold: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryApproved {
+    #[topic]
+    pub guardian: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryScheduled {
+    pub ready_at: u64,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryVetoed {
+    #[topic]
+    pub signer: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryCompleted {
+    pub signers: Vec<Address>,
+    pub threshold: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PauseThresholdUpdated {
+    pub threshold: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PauseVoteCast {
+    #[topic]
+    pub voter: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnpauseVoteCast {
+    #[topic]
+    pub voter: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Paused {
+    #[topic]
+    pub caller: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unpaused {
+    #[topic]
+    pub caller: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Upgraded {
+    pub wasm_hash: BytesN<32>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Migrated {
+    pub from: u32,
+    pub to: u32,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignerKeySet {
+    #[topic]
+    pub signer: Address,
+    pub key: SignerKey,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PayloadRevoked {
+    #[topic]
+    pub hash: BytesN<32>,
+    #[topic]
+    pub signer: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminUpdated {
+    pub admin: Option<Address>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionProposed {
+    #[topic]
+    pub tx_id: u64,
+    #[topic]
+    pub proposer: Address,
+    pub operations: Vec<Operation>,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApprovalCast {
+    #[topic]
+    pub tx_id: u64,
+    #[topic]
+    pub signer: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApprovalRevoked {
+    #[topic]
+    pub tx_id: u64,
+    #[topic]
+    pub signer: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RejectionCast {
+    #[topic]
+    pub tx_id: u64,
+    #[topic]
+    pub signer: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionExecuted {
+    #[topic]
+    pub tx_id: u64,
+    #[topic]
+    pub executor: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionRejected {
+    #[topic]
+    pub tx_id: u64,
+    #[topic]
+    pub signer: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionCancelled {
+    #[topic]
+    pub tx_id: u64,
+    #[topic]
+    pub caller: Address,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionQueued {
+    #[topic]
+    pub tx_id: u64,
+    pub eta: u64,
+}
+
+#[contractevent]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionExpired {
+    #[topic]
+    pub tx_id: u64,
 }
 
 #[contract]
@@ -42,185 +672,2448 @@ pub struct MultiSigContract;
 
 #[contractimpl]
 impl MultiSigContract {
-    
-    pub fn initialize(env: Env, admin: Address, signers: Vec<Address>, threshold: u32) {
-        assert!(!env.storage().persistent().has(&DataKey::Admin), "Contract already initialized");
-        let signers_len: u32 = signers.len().try_into().unwrap();
-        assert!(threshold > 0 && threshold <= signers_len, "Invalid threshold");
-        assert!(!signers.is_empty(), "At least one signer is required");
+    /// `proposal_lifetime` is the default number of seconds a proposal stays
+    /// open for approval; zero means proposals never expire unless the
+    /// proposer sets a lifetime. `execution_delay` is the number of seconds a
+    /// proposal stays queued after reaching the threshold; zero executes it
+    /// in the approving call. Passing no `admin` disables the single-key admin
+    /// entrypoints, so the signer set and threshold can only change through
+    /// proposals. Every initial signer has a weight of one; `threshold` is
+    /// the total weight of approvals a proposal needs.
+    pub fn initialize(
+        env: Env,
+        admin: Option<Address>,
+        signers: Vec<Address>,
+        threshold: u32,
+        proposal_lifetime: u64,
+        execution_delay: u64,
+    ) -> Result<(), Error> {
+        if Self::is_initialized(&env) {
+            return Err(Error::AlreadyInitialized);
+        }
+        if signers.is_empty() {
+            return Err(Error::NoSigners);
+        }
 
-        env.storage().persistent().set(&DataKey::Admin, &admin);
-        env.storage().persistent().set(&DataKey::Threshold, &threshold);
+        if let Some(admin) = &admin {
+            env.storage().instance().set(&DataKey::Admin, admin);
+        }
+        env.storage().instance().set(&DataKey::Threshold, &threshold);
 
         for signer in signers.iter() {
-            env.storage().persistent().set(&DataKey::Signer(signer.clone()), &true);
+            env.storage().persistent().set(&DataKey::Signer(signer.clone()), &1u32);
             Self::update_signers_list(&env, &signer, true);
         }
+        if threshold == 0 || threshold > Self::total_weight(&env) {
+            return Err(Error::InvalidThreshold);
+        }
+        Self::check_lifetime(proposal_lifetime, execution_delay)?;
+
+        env.storage().instance().set(&DataKey::NextId, &1u64);
+        env.storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &SCHEMA_VERSION);
+        Self::extend_instance(&env);
+        env.storage()
+            .instance()
+            .set(&DataKey::ProposalLifetime, &proposal_lifetime);
+        env.storage()
+            .instance()
+            .set(&DataKey::ExecutionDelay, &execution_delay);
 
-        env.storage().persistent().set(&DataKey::NextId, &1u64);
+        Initialized {
+            admin,
+            signers,
+            threshold,
+        }
+        .publish(&env);
+        Ok(())
     }
 
     // --- Authentication helpers ---
-    fn only_admin(env: &Env, caller: &Address) {
+    fn only_admin(env: &Env, caller: &Address) -> Result<(), Error> {
         caller.require_auth();
-        let admin: Address = env.storage().persistent().get(&DataKey::Admin).unwrap();
-        assert!(*caller == admin, "Caller is not the admin");
+        Self::extend_instance(env);
+        Self::when_migrated(env)?;
+        let admin = Self::get_admin(env).ok_or(Error::AdminDisabled)?;
+        if *caller != admin {
+            return Err(Error::NotAdmin);
+        }
+        Ok(())
     }
 
-    fn only_signer(env: &Env, caller: &Address) {
+    fn only_signer(env: &Env, caller: &Address) -> Result<(), Error> {
         caller.require_auth();
-        assert!(
-            env.storage().persistent().has(&DataKey::Signer(caller.clone())),
-            "Caller is not a signer"
-        );
+        Self::extend_instance(env);
+        Self::when_migrated(env)?;
+        let key = DataKey::Signer(caller.clone());
+        if !env.storage().persistent().has(&key) {
+            return Err(Error::NotSigner);
+        }
+        Self::extend_persistent(env, &key);
+        Self::extend_persistent(env, &DataKey::Signers);
+        Ok(())
     }
 
     // --- Admin functions ---
-    pub fn add_signer(env: Env, caller: Address, signer: Address) {
-        Self::only_admin(&env, &caller);
-        assert!(
-            !env.storage().persistent().has(&DataKey::Signer(signer.clone())),
-            "Signer already exists"
-        );
-
-        env.storage().persistent().set(&DataKey::Signer(signer.clone()), &true);
-        Self::update_signers_list(&env, &signer, true);
-    }
-
-    pub fn remove_signer(env: Env, caller: Address, signer: Address) {
-        Self::only_admin(&env, &caller);
-        let threshold: u32 = env.storage().persistent().get(&DataKey::Threshold).unwrap();
-        let current_signers = Self::get_signers(&env);
-        let current_len = current_signers.len() as i128;
-        let threshold_i128 = threshold as i128;
-        assert!(
-            current_len > threshold_i128,
-            "Cannot remove signer: would go below threshold"
-        );
+    pub fn add_signer(
+        env: Env,
+        caller: Address,
+        signer: Address,
+        weight: u32,
+    ) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_add_signer(&env, signer, weight)
+    }
 
-        env.storage().persistent().remove(&DataKey::Signer(signer.clone()));
-        Self::update_signers_list(&env, &signer, false);
+    pub fn remove_signer(env: Env, caller: Address, signer: Address) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_remove_signer(&env, signer)
     }
 
-    pub fn update_threshold(env: Env, caller: Address, new_threshold: u32) {
-        Self::only_admin(&env, &caller);
-        let current_signers = Self::get_signers(&env);
-        let current_len = current_signers.len() as u32;
-        assert!(
-            new_threshold > 0 && new_threshold <= current_len,
-            "Invalid threshold"
-        );
+    pub fn set_signer_weight(
+        env: Env,
+        caller: Address,
+        signer: Address,
+        weight: u32,
+    ) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_set_signer_weight(&env, signer, weight)
+    }
 
-        env.storage().persistent().set(&DataKey::Threshold, &new_threshold);
+    pub fn update_threshold(env: Env, caller: Address, new_threshold: u32) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_update_threshold(&env, new_threshold)
     }
 
-    // --- Transaction functions ---
-    pub fn propose_transaction(
+    /// Sets the members of a named signer group; an empty list deletes it.
+    pub fn set_group(
         env: Env,
         caller: Address,
-        to: Address,
-        amount: i128,
-        data: Bytes,
-    ) -> u64 {
-        Self::only_signer(&env, &caller);
-
-        let tx_id: u64 = env.storage().persistent().get(&DataKey::NextId).unwrap();
-        env.storage().persistent().set(&DataKey::NextId, &(tx_id + 1));
+        group: Symbol,
+        members: Vec<Address>,
+    ) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_set_group(&env, group, members)
+    }
 
-        let tx = Transaction {
-            id: tx_id,
-            to,
-            amount,
-            data,
-            status: TransactionStatus::Pending,
-            proposed_by: caller.clone(),
-            created_at: env.ledger().timestamp(),
-        };
+    /// Sets the per-group quorums every proposal must meet in addition to
+    /// the weight threshold.
+    pub fn set_group_quorums(
+        env: Env,
+        caller: Address,
+        quorums: Vec<GroupQuorum>,
+    ) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_set_group_quorums(&env, quorums)
+    }
 
-        env.storage().persistent().set(&DataKey::Transaction(tx_id), &tx);
+    /// Sets the amount tiers, ordered by strictly increasing `max_amount`.
+    /// Proposals above the last tier, or doing anything but transfers, use
+    /// the global threshold.
+    pub fn set_approval_tiers(
+        env: Env,
+        caller: Address,
+        tiers: Vec<ApprovalTier>,
+    ) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_set_approval_tiers(&env, tiers)
+    }
 
-        Self::self_approve(&env, &caller, tx_id);
+    /// Caps how much of `token` the vault may send per window, however many
+    /// approvals a proposal has.
+    pub fn set_spending_limit(
+        env: Env,
+        caller: Address,
+        token: Address,
+        limit: SpendingLimit,
+    ) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_set_spending_limit(&env, token, Some(limit))
+    }
 
-        tx_id
+    pub fn remove_spending_limit(env: Env, caller: Address, token: Address) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_set_spending_limit(&env, token, None)
     }
 
-    pub fn approve_transaction(env: Env, caller: Address, tx_id: u64) {
-        Self::only_signer(&env, &caller);
+    /// Sets how many signer or guardian votes pause the vault. Until set, a
+    /// single vote is enough.
+    pub fn set_pause_threshold(env: Env, caller: Address, threshold: u32) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_set_pause_threshold(&env, threshold)
+    }
 
-        let mut tx: Transaction = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Transaction(tx_id))
-            .unwrap_or_else(|| panic!("Transaction not found"));
+    /// Replaces the guardian set; an empty guardian list disables recovery.
+    /// Any recovery in progress is discarded.
+    pub fn set_guardians(env: Env, caller: Address, config: GuardianConfig) -> Result<(), Error> {
+        Self::only_admin(&env, &caller)?;
+        Self::apply_set_guardians(&env, config)
+    }
 
-        assert!(
-            tx.status == TransactionStatus::Pending,
-            "Transaction is not pending"
-        );
+    // --- Governance changes, shared by the admin path and proposals ---
+    fn apply_add_signer(env: &Env, signer: Address, weight: u32) -> Result<(), Error> {
+        if env.storage().persistent().has(&DataKey::Signer(signer.clone())) {
+            return Err(Error::SignerAlreadyExists);
+        }
+        // The combined weight must stay representable, so that summing any
+        // set of votes cannot overflow.
+        if weight == 0 || Self::total_weight(env).checked_add(weight).is_none() {
+            return Err(Error::InvalidWeight);
+        }
 
-        Self::self_approve(&env, &caller, tx_id);
+        env.storage().persistent().set(&DataKey::Signer(signer.clone()), &weight);
+        Self::update_signers_list(env, &signer, true);
+        let pending = Self::get_ids(env, &DataKey::StatusIndex(TransactionStatus::Pending));
+        for tx_id in pending.iter() {
+            if !Self::has_voted(env, tx_id, &signer) {
+                Self::insert_id(env, &DataKey::Inbox(signer.clone()), tx_id);
+            }
+        }
 
-        let approvals = Self::get_approvals(&env, tx_id);
-        let threshold: u32 = env.storage().persistent().get(&DataKey::Threshold).unwrap();
+        SignerAdded { signer, weight }.publish(env);
+        Ok(())
+    }
 
-        let threshold: u32 = env.storage().persistent().get(&DataKey::Threshold).unwrap();
-        if approvals.len() as u32 >= threshold {
-            Self::self_execute(&env, &mut tx);
+    fn apply_remove_signer(env: &Env, signer: Address) -> Result<(), Error> {
+        let weight = Self::get_signer_weight(env.clone(), signer.clone());
+        if weight == 0 {
+            return Err(Error::SignerNotFound);
+        }
+        Self::check_remaining_weight(env, Self::total_weight(env) - weight)?;
+        for quorum in Self::get_group_quorums(env.clone()).iter() {
+            let members = Self::get_group(env.clone(), quorum.group);
+            let mut remaining = Self::count_members(env, &members, &members);
+            if members.contains(&signer) {
+                remaining -= 1;
+            }
+            if remaining < quorum.min_approvals {
+                return Err(Error::GroupInUse);
+            }
         }
+
+        env.storage().persistent().remove(&DataKey::Signer(signer.clone()));
+        env.storage().persistent().remove(&DataKey::Inbox(signer.clone()));
+        env.storage().persistent().remove(&DataKey::SignerKey(signer.clone()));
+        Self::clear_allowances(env, &signer);
+        Self::update_signers_list(env, &signer, false);
+
+        SignerRemoved { signer }.publish(env);
+        Ok(())
     }
 
-    fn self_execute(env: &Env, tx: &mut Transaction) {
-        tx.status = TransactionStatus::Executed;
-        // Clone the transaction to avoid mutable reference issues
-        let tx_clone = tx.clone();
-        env.storage().persistent().set(&DataKey::Transaction(tx.id), &tx_clone);
+    fn apply_set_signer_weight(env: &Env, signer: Address, weight: u32) -> Result<(), Error> {
+        let current = Self::get_signer_weight(env.clone(), signer.clone());
+        if current == 0 {
+            return Err(Error::SignerNotFound);
+        }
+        let total = (Self::total_weight(env) - current).checked_add(weight);
+        let Some(total) = total.filter(|_| weight != 0) else {
+            return Err(Error::InvalidWeight);
+        };
+        Self::check_remaining_weight(env, total)?;
+
+        env.storage().persistent().set(&DataKey::Signer(signer.clone()), &weight);
 
+        SignerWeightUpdated { signer, weight }.publish(env);
+        Ok(())
     }
 
-    pub fn get_signers(env: &Env) -> Vec<Address> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Signers)
-            .unwrap_or_else(|| Vec::new(env))
+    /// Fails unless a combined weight of `total` still reaches the threshold
+    /// and every approval tier.
+    fn check_remaining_weight(env: &Env, total: u32) -> Result<(), Error> {
+        if total < Self::threshold(env)? {
+            return Err(Error::BelowThreshold);
+        }
+        for tier in Self::get_approval_tiers(env.clone()).iter() {
+            if total < tier.threshold {
+                return Err(Error::BelowThreshold);
+            }
+        }
+        Ok(())
     }
 
-    fn update_signers_list(env: &Env, signer: &Address, is_add: bool) {
-        let mut signers: Vec<Address> = env
-            .storage()
-            .persistent()
-            .get(&DataKey::Signers)
-            .unwrap_or_else(|| Vec::new(env));
+    fn apply_update_threshold(env: &Env, new_threshold: u32) -> Result<(), Error> {
+        if new_threshold == 0 || new_threshold > Self::total_weight(env) {
+            return Err(Error::InvalidThreshold);
+        }
 
-        if is_add {
-            if !signers.contains(signer) {
-                signers.push_back(signer.clone());
+        env.storage().instance().set(&DataKey::Threshold, &new_threshold);
+
+        ThresholdUpdated {
+            threshold: new_threshold,
+        }
+        .publish(env);
+        Ok(())
+    }
+
+    fn apply_set_group(env: &Env, group: Symbol, members: Vec<Address>) -> Result<(), Error> {
+        let mut groups = Self::get_groups(env.clone());
+        if members.is_empty() {
+            let index = groups.first_index_of(&group).ok_or(Error::GroupNotFound)?;
+            for quorum in Self::get_group_quorums(env.clone()).iter() {
+                if quorum.group == group {
+                    return Err(Error::GroupInUse);
+                }
             }
+            groups.remove(index);
+            env.storage().persistent().remove(&DataKey::Group(group.clone()));
         } else {
-            let mut new_signers = Vec::new(env);
-            for s in signers.iter() {
-                if &s != signer {
-                    new_signers.push_back(s);
+            for (i, member) in members.iter().enumerate() {
+                if members.first_index_of(&member) != Some(i as u32) {
+                    return Err(Error::SignerAlreadyExists);
+                }
+                if !env.storage().persistent().has(&DataKey::Signer(member)) {
+                    return Err(Error::SignerNotFound);
                 }
             }
-            env.storage().persistent().set(&DataKey::Signers, &new_signers);
+            for quorum in Self::get_group_quorums(env.clone()).iter() {
+                if quorum.group == group && quorum.min_approvals > members.len() {
+                    return Err(Error::InvalidQuorum);
+                }
+            }
+            if !groups.contains(&group) {
+                groups.push_back(group.clone());
+            }
+            let key = DataKey::Group(group.clone());
+            env.storage().persistent().set(&key, &members);
+            Self::extend_persistent(env, &key);
         }
+        env.storage().persistent().set(&DataKey::Groups, &groups);
+        Self::extend_persistent(env, &DataKey::Groups);
 
-        env.storage().persistent().set(&DataKey::Signers, &signers);
+        GroupUpdated { group, members }.publish(env);
+        Ok(())
     }
 
-    pub fn get_approvals(env: &Env, tx_id: u64) -> Vec<Address> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Approvals(tx_id))
-            .unwrap_or_else(|| Vec::new(env))
+    fn apply_set_group_quorums(env: &Env, quorums: Vec<GroupQuorum>) -> Result<(), Error> {
+        Self::validate_group_quorums(env, &quorums)?;
+        env.storage().instance().set(&DataKey::GroupQuorums, &quorums);
+
+        GroupQuorumsUpdated { quorums }.publish(env);
+        Ok(())
     }
 
-    pub fn get_transaction(env: Env, tx_id: u64) -> Option<Transaction> {
-        env.storage().persistent().get(&DataKey::Transaction(tx_id))
+    fn validate_group_quorums(env: &Env, quorums: &Vec<GroupQuorum>) -> Result<(), Error> {
+        for quorum in quorums.iter() {
+            let members: Vec<Address> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::Group(quorum.group))
+                .ok_or(Error::GroupNotFound)?;
+            if quorum.min_approvals == 0 || quorum.min_approvals > members.len() {
+                return Err(Error::InvalidQuorum);
+            }
+        }
+        Ok(())
     }
 
-    fn self_approve(env: &Env, caller: &Address, tx_id: u64) {
-        let mut approvals: Vec<Address> = env
+    fn apply_set_approval_tiers(env: &Env, tiers: Vec<ApprovalTier>) -> Result<(), Error> {
+        let total_weight = Self::total_weight(env);
+        let mut previous: Option<i128> = None;
+        for tier in tiers.iter() {
+            if tier.max_amount <= previous.unwrap_or(0)
+                || tier.threshold == 0
+                || tier.threshold > total_weight
+            {
+                return Err(Error::InvalidTiers);
+            }
+            previous = Some(tier.max_amount);
+        }
+        env.storage().instance().set(&DataKey::ApprovalTiers, &tiers);
+
+        ApprovalTiersUpdated { tiers }.publish(env);
+        Ok(())
+    }
+
+    fn apply_set_spending_limit(
+        env: &Env,
+        token: Address,
+        limit: Option<SpendingLimit>,
+    ) -> Result<(), Error> {
+        match &limit {
+            Some(limit) => {
+                if limit.amount <= 0 || limit.window == 0 {
+                    return Err(Error::InvalidSpendingLimit);
+                }
+                // Recorded spends are bucketed by the old window's length.
+                let previous = Self::get_spending_limit(env.clone(), token.clone());
+                if previous.is_some_and(|previous| previous.window != limit.window) {
+                    env.storage()
+                        .persistent()
+                        .remove(&DataKey::SpendingHistory(token.clone()));
+                }
+                let key = DataKey::SpendingLimit(token.clone());
+                env.storage().persistent().set(&key, limit);
+                Self::extend_persistent(env, &key);
+            }
+            None => {
+                env.storage()
+                    .persistent()
+                    .remove(&DataKey::SpendingLimit(token.clone()));
+                env.storage()
+                    .persistent()
+                    .remove(&DataKey::SpendingHistory(token.clone()));
+            }
+        }
+
+        SpendingLimitUpdated { token, limit }.publish(env);
+        Ok(())
+    }
+
+    /// Adds `amount` to the spending history of `token`, failing if the
+    /// spends still within the rolling window would exceed the limit.
+    fn record_spend(env: &Env, token: &Address, amount: i128) -> Result<(), Error> {
+        let limit: SpendingLimit = match env
+            .storage()
+            .persistent()
+            .get(&DataKey::SpendingLimit(token.clone()))
+        {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+
+        Self::carry_spending_window(env, token);
+        let bucket_len = limit.window.div_ceil(SPENDING_BUCKETS);
+        let current = env.ledger().timestamp() / bucket_len;
+        let mut history = Self::get_spending_history(env.clone(), token.clone())
+            .unwrap_or_else(|| SpendingHistory {
+                buckets: Map::new(env),
+            });
+        // Buckets are dropped only once they lie entirely outside the window.
+        let mut spent = amount;
+        for (bucket, bucket_spent) in history.buckets.clone().iter() {
+            if bucket + SPENDING_BUCKETS < current {
+                history.buckets.remove(bucket);
+            } else {
+                spent = spent.saturating_add(bucket_spent);
+            }
+        }
+        if spent > limit.amount {
+            return Err(Error::SpendingLimitExceeded);
+        }
+
+        let bucket_spent = history.buckets.get(current).unwrap_or(0);
+        history
+            .buckets
+            .set(current, bucket_spent.saturating_add(amount));
+        let key = DataKey::SpendingHistory(token.clone());
+        env.storage().persistent().set(&key, &history);
+        Self::extend_persistent(env, &key);
+        Self::extend_persistent(env, &DataKey::SpendingLimit(token.clone()));
+        Ok(())
+    }
+
+    /// Replaces the spending window `token` may have from before schema
+    /// version 8 with a bucket in its spending history. When within the
+    /// window the spends happened was not kept, so they count as made now.
+    fn carry_spending_window(env: &Env, token: &Address) {
+        let key = DataKey::SpendingWindow(token.clone());
+        let window: Option<SpendingWindow> = env.storage().persistent().get(&key);
+        let Some(window) = window else {
+            return;
+        };
+        env.storage().persistent().remove(&key);
+        let Some(limit) = Self::get_spending_limit(env.clone(), token.clone()) else {
+            return;
+        };
+        let now = env.ledger().timestamp();
+        if now >= window.start.saturating_add(limit.window) {
+            return;
+        }
+
+        let current = now / limit.window.div_ceil(SPENDING_BUCKETS);
+        let mut history = Self::get_spending_history(env.clone(), token.clone())
+            .unwrap_or_else(|| SpendingHistory {
+                buckets: Map::new(env),
+            });
+        let spent = history.buckets.get(current).unwrap_or(0);
+        history
+            .buckets
+            .set(current, spent.saturating_add(window.spent));
+        let key = DataKey::SpendingHistory(token.clone());
+        env.storage().persistent().set(&key, &history);
+        Self::extend_persistent(env, &key);
+    }
+
+    fn apply_grant_allowance(
+        env: &Env,
+        signer: Address,
+        token: Address,
+        allowance: Allowance,
+    ) -> Result<(), Error> {
+        if !env.storage().persistent().has(&DataKey::Signer(signer.clone())) {
+            return Err(Error::SignerNotFound);
+        }
+        if allowance.amount <= 0 || allowance.expires_at <= env.ledger().timestamp() {
+            return Err(Error::InvalidAllowance);
+        }
+
+        let key = DataKey::Allowance(signer.clone(), token.clone());
+        env.storage().persistent().set(&key, &allowance);
+        Self::extend_persistent(env, &key);
+        Self::track_allowance(env, &signer, &token);
+
+        AllowanceGranted {
+            signer,
+            token,
+            amount: allowance.amount,
+            expires_at: allowance.expires_at,
+        }
+        .publish(env);
+        Ok(())
+    }
+
+    fn apply_revoke_allowance(env: &Env, signer: Address, token: Address) -> Result<(), Error> {
+        let key = DataKey::Allowance(signer.clone(), token.clone());
+        if !env.storage().persistent().has(&key) {
+            return Err(Error::AllowanceNotFound);
+        }
+        env.storage().persistent().remove(&key);
+
+        let tokens_key = DataKey::AllowanceTokens(signer.clone());
+        let mut tokens = Self::get_allowance_tokens(env, &signer);
+        if let Some(index) = tokens.first_index_of(&token) {
+            tokens.remove(index);
+            if tokens.is_empty() {
+                env.storage().persistent().remove(&tokens_key);
+            } else {
+                env.storage().persistent().set(&tokens_key, &tokens);
+                Self::extend_persistent(env, &tokens_key);
+            }
+        }
+
+        AllowanceRevoked { signer, token }.publish(env);
+        Ok(())
+    }
+
+    fn get_allowance_tokens(env: &Env, signer: &Address) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AllowanceTokens(signer.clone()))
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    /// Adds `token` to the tokens `signer` holds an allowance of.
+    fn track_allowance(env: &Env, signer: &Address, token: &Address) {
+        let key = DataKey::AllowanceTokens(signer.clone());
+        let mut tokens = Self::get_allowance_tokens(env, signer);
+        if !tokens.contains(token) {
+            tokens.push_back(token.clone());
+            env.storage().persistent().set(&key, &tokens);
+        }
+        Self::extend_persistent(env, &key);
+    }
+
+    /// Drops every allowance `signer` holds, so none comes back should the
+    /// address be made a signer again.
+    fn clear_allowances(env: &Env, signer: &Address) {
+        for token in Self::get_allowance_tokens(env, signer).iter() {
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Allowance(signer.clone(), token.clone()));
+            AllowanceRevoked {
+                signer: signer.clone(),
+                token,
+            }
+            .publish(env);
+        }
+        env.storage()
+            .persistent()
+            .remove(&DataKey::AllowanceTokens(signer.clone()));
+    }
+
+    fn apply_set_allowlist_enabled(env: &Env, enabled: bool) {
+        env.storage()
+            .instance()
+            .set(&DataKey::AllowlistEnabled, &enabled);
+
+        AllowlistToggled { enabled }.publish(env);
+    }
+
+    fn apply_set_recipient_allowed(env: &Env, recipient: Address, allowed: bool) {
+        let key = DataKey::AllowedRecipient(recipient.clone());
+        if allowed {
+            env.storage().persistent().set(&key, &true);
+            Self::extend_persistent(env, &key);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+
+        RecipientAllowed { recipient, allowed }.publish(env);
+    }
+
+    fn apply_set_recipient_denied(env: &Env, recipient: Address, denied: bool) {
+        let key = DataKey::DeniedRecipient(recipient.clone());
+        if denied {
+            env.storage().persistent().set(&key, &true);
+            Self::extend_persistent(env, &key);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+
+        RecipientDenied { recipient, denied }.publish(env);
+    }
+
+    /// Rejects denylisted recipients, and recipients missing from the
+    /// allowlist while it is enabled.
+    fn check_recipient(env: &Env, to: &Address) -> Result<(), Error> {
+        if env
+            .storage()
+            .persistent()
+            .has(&DataKey::DeniedRecipient(to.clone()))
+        {
+            return Err(Error::RecipientDenied);
+        }
+        if Self::is_allowlist_enabled(env.clone()) {
+            let key = DataKey::AllowedRecipient(to.clone());
+            if !env.storage().persistent().has(&key) {
+                return Err(Error::RecipientNotAllowed);
+            }
+            Self::extend_persistent(env, &key);
+        }
+        Ok(())
+    }
+
+    /// Checks whoever a token call would let take funds from the vault: the
+    /// `to` of `transfer` and `transfer_from`, or the spender of `approve`.
+    /// A muxed recipient is checked as its underlying account, and a call
+    /// whose recipient is missing or not an address is refused. Other calls
+    /// are not checked.
+    fn check_call_recipient(env: &Env, function: &Symbol, args: &Vec<Val>) -> Result<(), Error> {
+        let index = if *function == Symbol::new(env, "transfer") {
+            1
+        } else if *function == Symbol::new(env, "transfer_from") {
+            2
+        } else if *function == Symbol::new(env, "approve") {
+            1
+        } else {
+            return Ok(());
+        };
+        let recipient = args
+            .get(index)
+            .and_then(|arg| MuxedAddress::try_from_val(env, &arg).ok())
+            .ok_or(Error::RecipientNotAllowed)?;
+        Self::check_recipient(env, &recipient.address())
+    }
+
+    fn apply_set_guardians(env: &Env, config: GuardianConfig) -> Result<(), Error> {
+        if config.guardians.is_empty() {
+            env.storage().persistent().remove(&DataKey::Guardians);
+        } else {
+            for (i, guardian) in config.guardians.iter().enumerate() {
+                if config.guardians.first_index_of(&guardian) != Some(i as u32) {
+                    return Err(Error::InvalidGuardians);
+                }
+            }
+            if config.threshold == 0
+                || config.threshold > config.guardians.len()
+                || config.delay < MIN_RECOVERY_DELAY
+            {
+                return Err(Error::InvalidGuardians);
+            }
+            env.storage().persistent().set(&DataKey::Guardians, &config);
+            Self::extend_persistent(env, &DataKey::Guardians);
+        }
+        env.storage().persistent().remove(&DataKey::Recovery);
+
+        GuardiansUpdated {
+            guardians: config.guardians,
+            threshold: config.threshold,
+            delay: config.delay,
+        }
+        .publish(env);
+        Ok(())
+    }
+
+    fn apply_set_pause_threshold(env: &Env, threshold: u32) -> Result<(), Error> {
+        if threshold == 0 || threshold > Self::pause_voters(env).len() {
+            return Err(Error::InvalidThreshold);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::PauseThreshold, &threshold);
+
+        PauseThresholdUpdated { threshold }.publish(env);
+        Ok(())
+    }
+
+    fn apply_set_admin(env: &Env, admin: Option<Address>) {
+        match &admin {
+            Some(admin) => env.storage().instance().set(&DataKey::Admin, admin),
+            None => env.storage().instance().remove(&DataKey::Admin),
+        }
+
+        AdminUpdated { admin }.publish(env);
+    }
+
+    // --- Transaction functions ---
+    pub fn propose_transaction(
+        env: Env,
+        caller: Address,
+        token: Address,
+        to: Address,
+        amount: i128,
+        data: Bytes,
+        lifetime: Option<u64>,
+    ) -> Result<u64, Error> {
+        Self::only_signer(&env, &caller)?;
+
+        let operation = Operation::Transfer(Transfer { token, to, amount });
+        Self::create_proposal(&env, &caller, vec![&env, operation], data, lifetime)
+    }
+
+    pub fn propose_invocation(
+        env: Env,
+        caller: Address,
+        contract: Address,
+        function: Symbol,
+        args: Vec<Val>,
+        data: Bytes,
+        lifetime: Option<u64>,
+    ) -> Result<u64, Error> {
+        Self::only_signer(&env, &caller)?;
+
+        let operation = Operation::Invoke(Invocation {
+            contract,
+            function,
+            args,
+        });
+        Self::create_proposal(&env, &caller, vec![&env, operation], data, lifetime)
+    }
+
+    /// Proposes several operations that execute in order within one call; if
+    /// any of them fails, the whole execution reverts.
+    pub fn propose_batch(
+        env: Env,
+        caller: Address,
+        operations: Vec<Operation>,
+        data: Bytes,
+        lifetime: Option<u64>,
+    ) -> Result<u64, Error> {
+        Self::only_signer(&env, &caller)?;
+        Self::create_proposal(&env, &caller, operations, data, lifetime)
+    }
+
+    /// `lifetime` overrides the vault's default proposal lifetime in seconds;
+    /// `Some(0)` opts the proposal out of expiry.
+    fn create_proposal(
+        env: &Env,
+        caller: &Address,
+        operations: Vec<Operation>,
+        data: Bytes,
+        lifetime: Option<u64>,
+    ) -> Result<u64, Error> {
+        Self::when_not_paused_for(env, &operations)?;
+        if operations.is_empty() {
+            return Err(Error::NoOperations);
+        }
+        if operations.len() > MAX_OPERATIONS {
+            return Err(Error::TooManyOperations);
+        }
+        for operation in operations.iter() {
+            Self::validate_operation(env, &operation)?;
+        }
+
+        let tx_id: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NextId)
+            .ok_or(Error::NotInitialized)?;
+        env.storage().instance().set(&DataKey::NextId, &(tx_id + 1));
+
+        let lifetime = match lifetime {
+            Some(lifetime) => lifetime,
+            None => env
+                .storage()
+                .instance()
+                .get(&DataKey::ProposalLifetime)
+                .unwrap_or(0),
+        };
+        Self::check_lifetime(
+            lifetime,
+            env.storage()
+                .instance()
+                .get(&DataKey::ExecutionDelay)
+                .unwrap_or(0),
+        )?;
+        let created_at = env.ledger().timestamp();
+        let expires_at = if lifetime > 0 {
+            Some(created_at.saturating_add(lifetime))
+        } else {
+            None
+        };
+
+        let tx = Transaction {
+            id: tx_id,
+            operations: operations.clone(),
+            data,
+            status: TransactionStatus::Pending,
+            proposed_by: caller.clone(),
+            created_at,
+            expires_at,
+            eta: None,
+        };
+
+        env.storage().persistent().set(&DataKey::Transaction(tx_id), &tx);
+        Self::extend_persistent(env, &DataKey::Transaction(tx_id));
+        Self::insert_id(env, &DataKey::StatusIndex(TransactionStatus::Pending), tx_id);
+
+        TransactionProposed {
+            tx_id,
+            proposer: caller.clone(),
+            operations,
+        }
+        .publish(env);
+        Self::self_approve(env, caller, tx_id);
+        Self::fill_inboxes(env, tx_id);
+
+        Ok(tx_id)
+    }
+
+    pub fn approve_transaction(env: Env, caller: Address, tx_id: u64) -> Result<(), Error> {
+        Self::only_signer(&env, &caller)?;
+        Self::record_approval(&env, &caller, tx_id)
+    }
+
+    /// Approves on behalf of an authenticated signer, executing or queueing
+    /// the proposal once it is approved.
+    fn record_approval(env: &Env, signer: &Address, tx_id: u64) -> Result<(), Error> {
+        let mut tx = Self::load_transaction(env, tx_id)?;
+        Self::when_not_paused_for(env, &tx.operations)?;
+        if tx.status != TransactionStatus::Pending {
+            return Err(Error::NotPending);
+        }
+        if Self::is_expired(env, &tx) {
+            return Err(Error::TransactionExpired);
+        }
+
+        Self::advance_vote_nonce(env, tx_id, signer);
+        Self::self_approve(env, signer, tx_id);
+        Self::remove_vote(env, &DataKey::Rejections(tx_id), signer);
+
+        if Self::is_approved(env, &tx)? {
+            Self::execute_or_queue(env, &mut tx, signer)?;
+        }
+        Ok(())
+    }
+
+    /// Executes an approved proposal right away, or queues it when the vault
+    /// has an execution delay. A proposal that would expire before its
+    /// timelock elapses cannot be queued.
+    fn execute_or_queue(env: &Env, tx: &mut Transaction, executor: &Address) -> Result<(), Error> {
+        let delay: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ExecutionDelay)
+            .unwrap_or(0);
+        if delay == 0 {
+            Self::self_execute(env, tx, executor)?;
+        } else {
+            let eta = env.ledger().timestamp().saturating_add(delay);
+            if tx.expires_at.is_some_and(|expires_at| eta >= expires_at) {
+                return Err(Error::TransactionExpired);
+            }
+            tx.eta = Some(eta);
+            Self::set_status(env, tx, TransactionStatus::Queued);
+            TransactionQueued { tx_id: tx.id, eta }.publish(env);
+        }
+        Ok(())
+    }
+
+    /// Executes a queued proposal once its timelock has elapsed. The
+    /// approvals are counted again so that signer and policy changes during
+    /// the delay are respected.
+    pub fn execute_transaction(env: Env, caller: Address, tx_id: u64) -> Result<(), Error> {
+        Self::only_signer(&env, &caller)?;
+
+        let mut tx = Self::load_transaction(&env, tx_id)?;
+        Self::when_not_paused_for(&env, &tx.operations)?;
+        if tx.status != TransactionStatus::Queued {
+            return Err(Error::NotQueued);
+        }
+        if env.ledger().timestamp() < tx.eta.unwrap_or(0) {
+            return Err(Error::TimelockNotElapsed);
+        }
+        if Self::is_expired(&env, &tx) {
+            return Err(Error::TransactionExpired);
+        }
+        if !Self::is_approved(&env, &tx)? {
+            return Err(Error::InsufficientApprovals);
+        }
+
+        Self::self_execute(&env, &mut tx, &caller)
+    }
+
+    /// Records a vote against the proposal. A signer who approved earlier
+    /// switches sides; once the remaining signers can no longer reach the
+    /// threshold or a group quorum, the proposal is marked `Rejected`. A
+    /// queued proposal that drops below the threshold returns to `Pending`.
+    pub fn reject_transaction(env: Env, caller: Address, tx_id: u64) -> Result<(), Error> {
+        Self::only_signer(&env, &caller)?;
+
+        let mut tx = Self::load_transaction(&env, tx_id)?;
+        match tx.status {
+            TransactionStatus::Pending => {
+                if Self::is_expired(&env, &tx) {
+                    return Err(Error::TransactionExpired);
+                }
+            }
+            TransactionStatus::Queued => {}
+            _ => return Err(Error::NotPending),
+        }
+
+        let mut rejections = Self::get_rejections(&env, tx_id);
+        if rejections.contains(&caller) {
+            return Err(Error::AlreadyRejected);
+        }
+        rejections.push_back(caller.clone());
+        env.storage().persistent().set(&DataKey::Rejections(tx_id), &rejections);
+        Self::advance_vote_nonce(&env, tx_id, &caller);
+        Self::remove_id(&env, &DataKey::Inbox(caller.clone()), tx_id);
+        if Self::remove_vote(&env, &DataKey::Approvals(tx_id), &caller) {
+            ApprovalRevoked {
+                tx_id,
+                signer: caller.clone(),
+            }
+            .publish(&env);
+        }
+        RejectionCast {
+            tx_id,
+            signer: caller.clone(),
+        }
+        .publish(&env);
+
+        if Self::is_unreachable(&env, &tx)? {
+            tx.eta = None;
+            Self::set_status(&env, &mut tx, TransactionStatus::Rejected);
+            TransactionRejected {
+                tx_id,
+                signer: caller,
+            }
+            .publish(&env);
+        } else {
+            Self::dequeue_if_unapproved(&env, &mut tx)?;
+        }
+        Ok(())
+    }
+
+    /// Withdraws the caller's approval from a pending or queued proposal. A
+    /// queued proposal that drops below the threshold returns to `Pending`.
+    pub fn revoke_approval(env: Env, caller: Address, tx_id: u64) -> Result<(), Error> {
+        Self::only_signer(&env, &caller)?;
+
+        let mut tx = Self::load_transaction(&env, tx_id)?;
+        if tx.status != TransactionStatus::Pending && tx.status != TransactionStatus::Queued {
+            return Err(Error::NotPending);
+        }
+        if !Self::remove_vote(&env, &DataKey::Approvals(tx_id), &caller) {
+            return Err(Error::NotApproved);
+        }
+        Self::advance_vote_nonce(&env, tx_id, &caller);
+        if tx.status == TransactionStatus::Pending {
+            Self::insert_id(&env, &DataKey::Inbox(caller.clone()), tx_id);
+        }
+
+        ApprovalRevoked {
+            tx_id,
+            signer: caller,
+        }
+        .publish(&env);
+        Self::dequeue_if_unapproved(&env, &mut tx)
+    }
+
+    fn dequeue_if_unapproved(env: &Env, tx: &mut Transaction) -> Result<(), Error> {
+        if tx.status == TransactionStatus::Queued && !Self::is_approved(env, tx)? {
+            tx.eta = None;
+            Self::set_status(env, tx, TransactionStatus::Pending);
+        }
+        Ok(())
+    }
+
+    /// Approval weight `tx` needs: the threshold of the lowest tier covering
+    /// its total transfer amount, or the global threshold when no tier does.
+    /// Amounts of different tokens are not comparable, so proposals moving
+    /// more than one token need the global threshold too.
+    fn required_threshold(env: &Env, tx: &Transaction) -> Result<u32, Error> {
+        let mut token: Option<Address> = None;
+        let mut amount: i128 = 0;
+        for operation in tx.operations.iter() {
+            match operation {
+                Operation::Transfer(transfer) => {
+                    if token.get_or_insert(transfer.token.clone()) != &transfer.token {
+                        return Self::threshold(env);
+                    }
+                    amount = amount.saturating_add(transfer.amount);
+                }
+                _ => return Self::threshold(env),
+            }
+        }
+        for tier in Self::get_approval_tiers(env.clone()).iter() {
+            if amount <= tier.max_amount {
+                return Ok(tier.threshold);
+            }
+        }
+        Self::threshold(env)
+    }
+
+    /// Whether the approvals on `tx` meet its weight threshold and every
+    /// group quorum.
+    fn is_approved(env: &Env, tx: &Transaction) -> Result<bool, Error> {
+        let approvals = Self::get_approvals(env, tx.id);
+        Ok(Self::meets_quorum(env, &approvals, Self::required_threshold(env, tx)?))
+    }
+
+    /// Whether `voters` carry `threshold` weight and satisfy every group quorum.
+    fn meets_quorum(env: &Env, voters: &Vec<Address>, threshold: u32) -> bool {
+        if Self::vote_weight(env, voters) < threshold {
+            return false;
+        }
+        Self::extend_persistent(env, &DataKey::Groups);
+        for quorum in Self::get_group_quorums(env.clone()).iter() {
+            Self::extend_persistent(env, &DataKey::Group(quorum.group.clone()));
+            let members = Self::get_group(env.clone(), quorum.group);
+            if Self::count_members(env, &members, voters) < quorum.min_approvals {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether the rejections on `tx` leave too little weight, or too few
+    /// members of some group, for it ever to be approved.
+    fn is_unreachable(env: &Env, tx: &Transaction) -> Result<bool, Error> {
+        let rejections = Self::get_rejections(env, tx.id);
+        let remaining = Self::total_weight(env) - Self::vote_weight(env, &rejections);
+        if remaining < Self::required_threshold(env, tx)? {
+            return Ok(true);
+        }
+        for quorum in Self::get_group_quorums(env.clone()).iter() {
+            let members = Self::get_group(env.clone(), quorum.group);
+            let all = Self::count_members(env, &members, &members);
+            let rejected = Self::count_members(env, &members, &rejections);
+            if all - rejected < quorum.min_approvals {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Withdraws a pending or queued proposal. Its proposer or the admin may
+    /// do so at any time, and any signer may veto a queued proposal during
+    /// its execution delay.
+    pub fn cancel_transaction(env: Env, caller: Address, tx_id: u64) -> Result<(), Error> {
+        caller.require_auth();
+        Self::extend_instance(&env);
+        Self::when_migrated(&env)?;
+
+        let mut tx = Self::load_transaction(&env, tx_id)?;
+        let is_veto = tx.status == TransactionStatus::Queued
+            && env.storage().persistent().has(&DataKey::Signer(caller.clone()));
+        if caller != tx.proposed_by && Some(caller.clone()) != Self::get_admin(&env) && !is_veto {
+            return Err(Error::NotProposerOrAdmin);
+        }
+        if tx.status != TransactionStatus::Pending && tx.status != TransactionStatus::Queued {
+            return Err(Error::NotPending);
+        }
+
+        tx.eta = None;
+        Self::set_status(&env, &mut tx, TransactionStatus::Cancelled);
+
+        TransactionCancelled { tx_id, caller }.publish(&env);
+        Ok(())
+    }
+
+    /// Sends `amount` of `token` to `to` out of the caller's allowance,
+    /// without a proposal. The vault's spending limits still apply.
+    pub fn spend_allowance(
+        env: Env,
+        caller: Address,
+        token: Address,
+        to: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        Self::only_signer(&env, &caller)?;
+        Self::when_not_paused(&env)?;
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        Self::check_recipient(&env, &to)?;
+
+        let key = DataKey::Allowance(caller.clone(), token.clone());
+        let mut allowance: Allowance = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .ok_or(Error::AllowanceNotFound)?;
+        if env.ledger().timestamp() >= allowance.expires_at {
+            return Err(Error::AllowanceExpired);
+        }
+        if amount > allowance.amount {
+            return Err(Error::AllowanceExceeded);
+        }
+
+        allowance.amount -= amount;
+        env.storage().persistent().set(&key, &allowance);
+        Self::extend_persistent(&env, &key);
+        Self::record_spend(&env, &token, amount)?;
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+
+        AllowanceSpent {
+            signer: caller,
+            token,
+            to,
+            amount,
+        }
+        .publish(&env);
+        Ok(())
+    }
+
+    // --- Off-chain signatures ---
+    /// Registers the key the caller signs proposals with off-chain.
+    /// `signature` proves possession of the key: it must sign the SHA-256 of
+    /// the XDR of `(Symbol("signer_key"), vault address, caller)`.
+    pub fn set_signer_key(
+        env: Env,
+        caller: Address,
+        key: SignerKey,
+        signature: SignerSignature,
+    ) -> Result<(), Error> {
+        Self::only_signer(&env, &caller)?;
+
+        let preimage = (
+            Symbol::new(&env, "signer_key"),
+            env.current_contract_address(),
+            caller.clone(),
+        )
+            .to_xdr(&env);
+        Self::verify_key(&env, &key, &env.crypto().sha256(&preimage), &signature)?;
+
+        let storage_key = DataKey::SignerKey(caller.clone());
+        env.storage().persistent().set(&storage_key, &key);
+        Self::extend_persistent(&env, &storage_key);
+
+        SignerKeySet {
+            signer: caller,
+            key,
+        }
+        .publish(&env);
+        Ok(())
+    }
+
+    /// Id the next proposal will get.
+    pub fn get_next_id(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::NextId)
+            .unwrap_or(1)
+    }
+
+    pub fn get_signer_key(env: Env, signer: Address) -> Option<SignerKey> {
+        env.storage().persistent().get(&DataKey::SignerKey(signer))
+    }
+
+    /// The hash signers sign to approve `payload` off-chain: SHA-256 of the
+    /// XDR of `(vault address, payload)`.
+    pub fn proposal_hash(env: Env, payload: ProposalPayload) -> BytesN<32> {
+        Self::hash_payload(&env, payload).into()
+    }
+
+    /// The hash `signer` signs to approve the on-chain proposal `tx_id`
+    /// off-chain: SHA-256 of the XDR of `(vault address, ApprovalPayload)`.
+    /// It changes with every vote the signer casts on the proposal.
+    pub fn approval_hash(env: Env, tx_id: u64, signer: Address) -> Result<BytesN<32>, Error> {
+        let tx = Self::load_transaction(&env, tx_id)?;
+        let nonce = Self::get_vote_nonce(&env, tx_id, &signer);
+        Ok(Self::hash_approval(&env, tx, nonce).into())
+    }
+
+    fn hash_payload(env: &Env, payload: ProposalPayload) -> Hash<32> {
+        let preimage = (env.current_contract_address(), payload).to_xdr(env);
+        env.crypto().sha256(&preimage)
+    }
+
+    fn hash_approval(env: &Env, tx: Transaction, nonce: u32) -> Hash<32> {
+        let payload = ApprovalPayload {
+            tx_id: tx.id,
+            nonce,
+            operations: tx.operations,
+            data: tx.data,
+        };
+        let preimage = (env.current_contract_address(), payload).to_xdr(env);
+        env.crypto().sha256(&preimage)
+    }
+
+    /// Approves a pending proposal with a signature from the signer's
+    /// registered key over its `approval_hash`, so signers holding only a
+    /// passkey can have a relayer submit their approval. Anyone may submit.
+    /// Each signature casts one vote: once used, or once the signer votes
+    /// otherwise on the proposal, it no longer verifies.
+    pub fn approve_with_signature(
+        env: Env,
+        signer: Address,
+        tx_id: u64,
+        signature: SignerSignature,
+    ) -> Result<(), Error> {
+        Self::extend_instance(&env);
+        Self::when_migrated(&env)?;
+        let tx = Self::load_transaction(&env, tx_id)?;
+        let nonce = Self::get_vote_nonce(&env, tx_id, &signer);
+        let digest = Self::hash_approval(&env, tx, nonce);
+        Self::verify_signature(&env, &signer, &digest, &signature)?;
+        Self::record_approval(&env, &signer, tx_id)
+    }
+
+    fn get_vote_nonce(env: &Env, tx_id: u64, signer: &Address) -> u32 {
+        let nonces: Map<Address, u32> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::VoteNonces(tx_id))
+            .unwrap_or_else(|| Map::new(env));
+        nonces.get(signer.clone()).unwrap_or(0)
+    }
+
+    /// Invalidates any approval signature `signer` has given out for
+    /// `tx_id`; called for every vote they cast or withdraw on it.
+    fn advance_vote_nonce(env: &Env, tx_id: u64, signer: &Address) {
+        let key = DataKey::VoteNonces(tx_id);
+        let mut nonces: Map<Address, u32> = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| Map::new(env));
+        let nonce = nonces.get(signer.clone()).unwrap_or(0);
+        nonces.set(signer.clone(), nonce + 1);
+        env.storage().persistent().set(&key, &nonces);
+        Self::extend_persistent(env, &key);
+    }
+
+    /// Records a proposal and all its approvals in one call from signatures
+    /// collected off-chain, then executes it, or queues it when the vault
+    /// has an execution delay. The first signer is recorded as proposer.
+    /// The signatures must reach the approval threshold, and any invalid
+    /// signature aborts the call. Anyone may submit, once per payload and
+    /// until its `valid_until`.
+    pub fn execute_with_signatures(
+        env: Env,
+        payload: ProposalPayload,
+        signatures: Vec<(Address, SignerSignature)>,
+    ) -> Result<u64, Error> {
+        Self::threshold(&env)?;
+        Self::when_migrated(&env)?;
+        let (proposer, _) = signatures.first().ok_or(Error::InsufficientApprovals)?;
+        if env.ledger().timestamp() > payload.valid_until {
+            return Err(Error::TransactionExpired);
+        }
+
+        let digest = Self::hash_payload(&env, payload.clone());
+        let executed = DataKey::ExecutedPayload(digest.to_bytes());
+        if env.storage().persistent().has(&executed) {
+            return Err(Error::SignatureReplayed);
+        }
+        for (signer, signature) in signatures.iter() {
+            Self::verify_signature(&env, &signer, &digest, &signature)?;
+        }
+        Self::extend_instance(&env);
+        env.storage().persistent().set(&executed, &true);
+        Self::extend_persistent(&env, &executed);
+
+        let tx_id =
+            Self::create_proposal(&env, &proposer, payload.operations, payload.data, None)?;
+        for (signer, _) in signatures.iter() {
+            Self::self_approve(&env, &signer, tx_id);
+        }
+
+        let mut tx = Self::load_transaction(&env, tx_id)?;
+        if !Self::is_approved(&env, &tx)? {
+            return Err(Error::InsufficientApprovals);
+        }
+        Self::execute_or_queue(&env, &mut tx, &proposer)?;
+        Ok(tx_id)
+    }
+
+    /// Burns the payload with `hash`, as returned by `proposal_hash`, so
+    /// signatures already collected for it can no longer be submitted.
+    pub fn revoke_payload(env: Env, caller: Address, hash: BytesN<32>) -> Result<(), Error> {
+        Self::only_signer(&env, &caller)?;
+        let key = DataKey::ExecutedPayload(hash.clone());
+        if env.storage().persistent().has(&key) {
+            return Err(Error::SignatureReplayed);
+        }
+        env.storage().persistent().set(&key, &true);
+        Self::extend_persistent(&env, &key);
+
+        PayloadRevoked {
+            hash,
+            signer: caller,
+        }
+        .publish(&env);
+        Ok(())
+    }
+
+    /// Checks `signature` over `digest` against the signer's registered key;
+    /// an invalid signature traps.
+    fn verify_signature(
+        env: &Env,
+        signer: &Address,
+        digest: &Hash<32>,
+        signature: &SignerSignature,
+    ) -> Result<(), Error> {
+        if !env.storage().persistent().has(&DataKey::Signer(signer.clone())) {
+            return Err(Error::NotSigner);
+        }
+        let key = Self::get_signer_key(env.clone(), signer.clone())
+            .ok_or(Error::SignerKeyNotFound)?;
+        Self::verify_key(env, &key, digest, signature)
+    }
+
+    fn verify_key(
+        env: &Env,
+        key: &SignerKey,
+        digest: &Hash<32>,
+        signature: &SignerSignature,
+    ) -> Result<(), Error> {
+        match (key, signature) {
+            (SignerKey::Ed25519(public_key), SignerSignature::Ed25519(signature)) => {
+                env.crypto()
+                    .ed25519_verify(public_key, &Bytes::from(digest.clone()), signature);
+            }
+            (SignerKey::Secp256r1(public_key), SignerSignature::Secp256r1(assertion)) => {
+                Self::check_client_data(&assertion.client_data_json, digest)?;
+                // Byte 32 holds the flags; bit 0 is set once the user was present.
+                let flags = assertion.authenticator_data.get(32).unwrap_or(0);
+                if flags & 1 == 0 {
+                    return Err(Error::InvalidSignature);
+                }
+                let mut message = assertion.authenticator_data.clone();
+                message.append(&env.crypto().sha256(&assertion.client_data_json).into());
+                let message_digest = env.crypto().sha256(&message);
+                env.crypto()
+                    .secp256r1_verify(public_key, &message_digest, &assertion.signature);
+            }
+            _ => return Err(Error::InvalidSignature),
+        }
+        Ok(())
+    }
+
+    /// Checks that WebAuthn client data is for an assertion whose challenge
+    /// is `digest`. Browsers serialize it with its keys unspaced, in order.
+    fn check_client_data(client_data_json: &Bytes, digest: &Hash<32>) -> Result<(), Error> {
+        let len = client_data_json.len() as usize;
+        if len > MAX_CLIENT_DATA_LEN {
+            return Err(Error::InvalidSignature);
+        }
+        let mut buffer = [0u8; MAX_CLIENT_DATA_LEN];
+        client_data_json.copy_into_slice(&mut buffer[..len]);
+        let json = &buffer[..len];
+
+        let mut challenge = [0u8; 57];
+        challenge[..13].copy_from_slice(b"\"challenge\":\"");
+        Self::base64url(&digest.to_array(), &mut challenge[13..56]);
+        challenge[56] = b'"';
+
+        let contains = |needle: &[u8]| json.windows(needle.len()).any(|window| window == needle);
+        if !contains(b"\"type\":\"webauthn.get\"") || !contains(&challenge) {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    /// Writes `input` to `output` in unpadded base64url.
+    fn base64url(input: &[u8], output: &mut [u8]) {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+        let (mut bits, mut pending, mut written) = (0u32, 0u32, 0);
+        for byte in input {
+            bits = (bits << 8) | *byte as u32;
+            pending += 8;
+            while pending >= 6 {
+                pending -= 6;
+                output[written] = ALPHABET[((bits >> pending) & 63) as usize];
+                written += 1;
+            }
+        }
+        if pending > 0 {
+            output[written] = ALPHABET[((bits << (6 - pending)) & 63) as usize];
+        }
+    }
+
+    // --- Upgrades ---
+    /// Brings storage written by older code up to `SCHEMA_VERSION`, one
+    /// version at a time. Vaults from before versioning count as version
+    /// zero. Anyone may call this; it does nothing once storage is current.
+    /// Steps visiting every transaction stop after `MIGRATION_BATCH` of them
+    /// and resume on the next call. Until storage is current, entrypoints
+    /// that change state refuse to run.
+    pub fn migrate(env: Env) -> Result<(), Error> {
+        if !Self::is_initialized(&env) {
+            return Err(Error::NotInitialized);
+        }
+        Self::extend_instance(&env);
+        let from = Self::get_schema_version(env.clone());
+        if from > SCHEMA_VERSION {
+            return Err(Error::UnknownSchemaVersion);
+        }
+
+        for version in from..SCHEMA_VERSION {
+            match version {
+                // Version 1 only introduced the version key itself.
+                0 => {}
+                // Version 2 moved the vault-wide settings to instance storage.
+                1 => {
+                    for key in [DataKey::Admin, DataKey::Threshold, DataKey::NextId] {
+                        let value: Option<Val> = env.storage().persistent().get(&key);
+                        if let Some(value) = value {
+                            env.storage().instance().set(&key, &value);
+                            env.storage().persistent().remove(&key);
+                        }
+                    }
+                }
+                // Version 3 indexes transactions by status.
+                2 => {
+                    let unfinished = Self::migrate_batch(&env, version, |tx| {
+                        if Self::is_indexed(&tx.status) {
+                            Self::insert_id(&env, &DataKey::StatusIndex(tx.status), tx.id);
+                        }
+                    });
+                    if unfinished {
+                        return Ok(());
+                    }
+                }
+                // Version 4 keeps an inbox of pending transactions per signer.
+                3 => {
+                    let key = DataKey::StatusIndex(TransactionStatus::Pending);
+                    for tx_id in Self::get_ids(&env, &key).iter() {
+                        Self::fill_inboxes(&env, tx_id);
+                    }
+                }
+                // Version 5 tags signer keys with their curve.
+                4 => {
+                    for signer in Self::get_signers(&env).iter() {
+                        let key = DataKey::SignerKey(signer);
+                        let public_key: Option<BytesN<32>> = env.storage().persistent().get(&key);
+                        if let Some(public_key) = public_key {
+                            env.storage()
+                                .persistent()
+                                .set(&key, &SignerKey::Ed25519(public_key));
+                        }
+                    }
+                }
+                // Version 6 moves the remaining vault-wide settings to
+                // instance storage, which lives as long as the contract.
+                5 => {
+                    for key in [
+                        DataKey::ProposalLifetime,
+                        DataKey::ExecutionDelay,
+                        DataKey::Paused,
+                        DataKey::PauseThreshold,
+                        DataKey::GroupQuorums,
+                        DataKey::ApprovalTiers,
+                        DataKey::AllowlistEnabled,
+                    ] {
+                        let value: Option<Val> = env.storage().persistent().get(&key);
+                        if let Some(value) = value {
+                            env.storage().instance().set(&key, &value);
+                            env.storage().persistent().remove(&key);
+                        }
+                    }
+                }
+                // Version 7 stops indexing closed transactions, whose indexes
+                // only ever grew.
+                6 => {
+                    for status in [
+                        TransactionStatus::Executed,
+                        TransactionStatus::Rejected,
+                        TransactionStatus::Cancelled,
+                        TransactionStatus::Expired,
+                    ] {
+                        env.storage()
+                            .persistent()
+                            .remove(&DataKey::StatusIndex(status));
+                    }
+                }
+                // Version 8 keeps spends per sub-window. The spending window
+                // of each token a transaction moved, limited or granted is
+                // carried over; others carry over on the token's next spend.
+                7 => {
+                    let unfinished = Self::migrate_batch(&env, version, |tx| {
+                        for operation in tx.operations.iter() {
+                            let token = match operation {
+                                Operation::Transfer(transfer) => transfer.token,
+                                Operation::SetSpendingLimit(token, _)
+                                | Operation::GrantAllowance(_, token, _) => token,
+                                _ => continue,
+                            };
+                            Self::carry_spending_window(&env, &token);
+                        }
+                    });
+                    if unfinished {
+                        return Ok(());
+                    }
+                }
+                // Version 9 lists the tokens each signer holds an allowance
+                // of, so removing the signer clears them. Allowances left
+                // behind by signers removed earlier are dropped.
+                8 => {
+                    let unfinished = Self::migrate_batch(&env, version, |tx| {
+                        for operation in tx.operations.iter() {
+                            let Operation::GrantAllowance(signer, token, _) = operation else {
+                                continue;
+                            };
+                            let key = DataKey::Allowance(signer.clone(), token.clone());
+                            if !env.storage().persistent().has(&key) {
+                                continue;
+                            }
+                            if env.storage().persistent().has(&DataKey::Signer(signer.clone())) {
+                                Self::track_allowance(&env, &signer, &token);
+                            } else {
+                                env.storage().persistent().remove(&key);
+                            }
+                        }
+                    });
+                    if unfinished {
+                        return Ok(());
+                    }
+                }
+                _ => return Err(Error::UnknownSchemaVersion),
+            }
+        }
+        if from < SCHEMA_VERSION {
+            Self::set_schema_version(&env, SCHEMA_VERSION);
+            Migrated {
+                from,
+                to: SCHEMA_VERSION,
+            }
+            .publish(&env);
+        }
+        Ok(())
+    }
+
+    /// Vaults awaiting migration still keep their threshold in persistent
+    /// storage.
+    fn is_initialized(env: &Env) -> bool {
+        env.storage().instance().has(&DataKey::Threshold)
+            || env.storage().persistent().has(&DataKey::Threshold)
+    }
+
+    /// Vaults before version 6 keep their version in persistent storage.
+    pub fn get_schema_version(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::SchemaVersion)
+            .or_else(|| env.storage().persistent().get(&DataKey::SchemaVersion))
+            .unwrap_or(0)
+    }
+
+    /// Hands the next `MIGRATION_BATCH` transactions to `visit`, after those
+    /// earlier calls visited. Returns whether any are left, leaving storage
+    /// at `version` for the next call to resume from.
+    fn migrate_batch(env: &Env, version: u32, mut visit: impl FnMut(Transaction)) -> bool {
+        let next_id: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NextId)
+            .unwrap_or(1);
+        let cursor: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::MigrationCursor)
+            .unwrap_or(1);
+        let end = next_id.min(cursor.saturating_add(MIGRATION_BATCH));
+        for tx_id in cursor..end {
+            if let Some(tx) = Self::get_transaction(env.clone(), tx_id) {
+                visit(tx);
+            }
+        }
+        if end < next_id {
+            env.storage().instance().set(&DataKey::MigrationCursor, &end);
+            Self::set_schema_version(env, version);
+            return true;
+        }
+        env.storage().instance().remove(&DataKey::MigrationCursor);
+        false
+    }
+
+    fn set_schema_version(env: &Env, version: u32) {
+        env.storage().instance().set(&DataKey::SchemaVersion, &version);
+        env.storage().persistent().remove(&DataKey::SchemaVersion);
+    }
+
+    /// Refuses to act on storage older code wrote until `migrate` has
+    /// brought it up to date.
+    fn when_migrated(env: &Env) -> Result<(), Error> {
+        if Self::get_schema_version(env.clone()) != SCHEMA_VERSION {
+            return Err(Error::UnknownSchemaVersion);
+        }
+        Ok(())
+    }
+
+    // --- Emergency pause ---
+    fn when_not_paused(env: &Env) -> Result<(), Error> {
+        if Self::is_paused(env.clone()) {
+            return Err(Error::ContractPaused);
+        }
+        Ok(())
+    }
+
+    /// Like `when_not_paused`, but lets through proposals made only of
+    /// operations that take pausing away from a voter: removing a signer,
+    /// replacing the guardians or changing the pause threshold. Without an
+    /// admin, this is how signers stop a single voter re-pausing the vault
+    /// every time it is unpaused.
+    fn when_not_paused_for(env: &Env, operations: &Vec<Operation>) -> Result<(), Error> {
+        let restores_pause_control = !operations.is_empty()
+            && operations.iter().all(|operation| {
+                matches!(
+                    operation,
+                    Operation::RemoveSigner(_)
+                        | Operation::SetGuardians(_)
+                        | Operation::SetPauseThreshold(_)
+                )
+            });
+        if restores_pause_control {
+            return Ok(());
+        }
+        Self::when_not_paused(env)
+    }
+
+    /// Current signers and guardians, each listed once.
+    fn pause_voters(env: &Env) -> Vec<Address> {
+        let mut voters = Self::get_signers(env);
+        if let Some(config) = Self::get_guardians(env.clone()) {
+            for guardian in config.guardians.iter() {
+                if !voters.contains(&guardian) {
+                    voters.push_back(guardian);
+                }
+            }
+        }
+        voters
+    }
+
+    /// Votes to pause the vault, freezing proposals, approvals, execution
+    /// and allowance spending. Signers and guardians may vote; the vault
+    /// pauses once the pause threshold is reached. Votes of since-removed
+    /// signers and guardians no longer count, and the threshold is capped
+    /// at the number of those remaining.
+    pub fn pause(env: Env, caller: Address) -> Result<(), Error> {
+        caller.require_auth();
+        Self::extend_instance(&env);
+        Self::when_migrated(&env)?;
+        let voters = Self::pause_voters(&env);
+        if !voters.contains(&caller) {
+            return Err(Error::NotSigner);
+        }
+        Self::when_not_paused(&env)?;
+
+        let mut votes = Vec::new(&env);
+        for voter in Self::get_pause_votes(env.clone()).iter() {
+            if voters.contains(&voter) {
+                votes.push_back(voter);
+            }
+        }
+        if !votes.contains(&caller) {
+            votes.push_back(caller.clone());
+            PauseVoteCast {
+                voter: caller.clone(),
+            }
+            .publish(&env);
+        }
+
+        let threshold = Self::get_pause_threshold(env.clone()).min(voters.len());
+        if votes.len() >= threshold {
+            env.storage().instance().set(&DataKey::Paused, &true);
+            env.storage().persistent().remove(&DataKey::PauseVotes);
+            Paused { caller }.publish(&env);
+        } else {
+            env.storage().persistent().set(&DataKey::PauseVotes, &votes);
+        }
+        Ok(())
+    }
+
+    /// Lifts a pause. The admin can do so alone; otherwise signers vote
+    /// until their combined weight reaches the threshold.
+    pub fn unpause(env: Env, caller: Address) -> Result<(), Error> {
+        if Self::get_admin(&env) == Some(caller.clone()) {
+            caller.require_auth();
+            Self::when_migrated(&env)?;
+        } else {
+            Self::only_signer(&env, &caller)?;
+        }
+        if !Self::is_paused(env.clone()) {
+            return Err(Error::NotPaused);
+        }
+
+        if Self::get_admin(&env) != Some(caller.clone()) {
+            let mut votes: Vec<Address> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::UnpauseVotes)
+                .unwrap_or_else(|| Vec::new(&env));
+            if !votes.contains(&caller) {
+                votes.push_back(caller.clone());
+                UnpauseVoteCast {
+                    voter: caller.clone(),
+                }
+                .publish(&env);
+            }
+            if Self::vote_weight(&env, &votes) < Self::threshold(&env)? {
+                env.storage().persistent().set(&DataKey::UnpauseVotes, &votes);
+                return Ok(());
+            }
+        }
+
+        env.storage().instance().remove(&DataKey::Paused);
+        env.storage().persistent().remove(&DataKey::UnpauseVotes);
+        Unpaused { caller }.publish(&env);
+        Ok(())
+    }
+
+    // --- Social recovery ---
+    fn only_guardian(env: &Env, caller: &Address) -> Result<GuardianConfig, Error> {
+        caller.require_auth();
+        Self::extend_instance(env);
+        Self::when_migrated(env)?;
+        let config = Self::get_guardians(env.clone()).ok_or(Error::NotGuardian)?;
+        if !config.guardians.contains(caller) {
+            return Err(Error::NotGuardian);
+        }
+        Self::extend_persistent(env, &DataKey::Guardians);
+        Self::extend_persistent(env, &DataKey::Recovery);
+        Ok(config)
+    }
+
+    /// Starts replacing the signer set with `signers`, each of weight one,
+    /// and `threshold`. Only one recovery can be in progress at a time.
+    pub fn initiate_recovery(
+        env: Env,
+        caller: Address,
+        signers: Vec<Address>,
+        threshold: u32,
+    ) -> Result<(), Error> {
+        let config = Self::only_guardian(&env, &caller)?;
+        if env.storage().persistent().has(&DataKey::Recovery) {
+            return Err(Error::RecoveryInProgress);
+        }
+        if signers.is_empty() {
+            return Err(Error::NoSigners);
+        }
+        let mut unique = Vec::new(&env);
+        for signer in signers.iter() {
+            if !unique.contains(&signer) {
+                unique.push_back(signer);
+            }
+        }
+        if threshold == 0 || threshold > unique.len() {
+            return Err(Error::InvalidThreshold);
+        }
+
+        let mut recovery = Recovery {
+            signers: unique,
+            threshold,
+            approvals: Vec::new(&env),
+            ready_at: None,
+        };
+        RecoveryInitiated {
+            guardian: caller.clone(),
+            signers: recovery.signers.clone(),
+            threshold,
+        }
+        .publish(&env);
+        Self::record_recovery_approval(&env, &config, &mut recovery, &caller);
+        Ok(())
+    }
+
+    pub fn approve_recovery(env: Env, caller: Address) -> Result<(), Error> {
+        let config = Self::only_guardian(&env, &caller)?;
+        let mut recovery = Self::get_recovery(env.clone()).ok_or(Error::RecoveryNotFound)?;
+        Self::record_recovery_approval(&env, &config, &mut recovery, &caller);
+        Ok(())
+    }
+
+    /// Adds `guardian`'s approval and starts the delay once the guardian
+    /// threshold is reached.
+    fn record_recovery_approval(
+        env: &Env,
+        config: &GuardianConfig,
+        recovery: &mut Recovery,
+        guardian: &Address,
+    ) {
+        if !recovery.approvals.contains(guardian) {
+            recovery.approvals.push_back(guardian.clone());
+            RecoveryApproved {
+                guardian: guardian.clone(),
+            }
+            .publish(env);
+        }
+        if recovery.ready_at.is_none() && recovery.approvals.len() >= config.threshold {
+            let ready_at = env.ledger().timestamp().saturating_add(config.delay);
+            recovery.ready_at = Some(ready_at);
+            RecoveryScheduled { ready_at }.publish(env);
+        }
+        env.storage().persistent().set(&DataKey::Recovery, recovery);
+        Self::extend_persistent(env, &DataKey::Recovery);
+    }
+
+    /// Lets any current signer cancel a recovery in progress.
+    pub fn veto_recovery(env: Env, caller: Address) -> Result<(), Error> {
+        Self::only_signer(&env, &caller)?;
+        if !env.storage().persistent().has(&DataKey::Recovery) {
+            return Err(Error::RecoveryNotFound);
+        }
+        env.storage().persistent().remove(&DataKey::Recovery);
+
+        RecoveryVetoed { signer: caller }.publish(&env);
+        Ok(())
+    }
+
+    /// Installs the recovered signer set once the delay has elapsed. Group
+    /// quorums and approval tiers were sized for the old signers, so they
+    /// are cleared, as are the old signers' allowances. Anyone may call this.
+    pub fn complete_recovery(env: Env) -> Result<(), Error> {
+        Self::extend_instance(&env);
+        Self::when_migrated(&env)?;
+        let recovery = Self::get_recovery(env.clone()).ok_or(Error::RecoveryNotFound)?;
+        match recovery.ready_at {
+            Some(ready_at) if env.ledger().timestamp() >= ready_at => {}
+            _ => return Err(Error::RecoveryNotReady),
+        }
+
+        for signer in Self::get_signers(&env).iter() {
+            env.storage().persistent().remove(&DataKey::Signer(signer.clone()));
+            env.storage().persistent().remove(&DataKey::Inbox(signer.clone()));
+            env.storage().persistent().remove(&DataKey::SignerKey(signer.clone()));
+            Self::clear_allowances(&env, &signer);
+        }
+        for signer in recovery.signers.iter() {
+            let key = DataKey::Signer(signer);
+            env.storage().persistent().set(&key, &1u32);
+            Self::extend_persistent(&env, &key);
+        }
+        env.storage()
+            .persistent()
+            .set(&DataKey::Signers, &recovery.signers);
+        env.storage()
+            .instance()
+            .set(&DataKey::Threshold, &recovery.threshold);
+        env.storage().instance().remove(&DataKey::GroupQuorums);
+        env.storage().instance().remove(&DataKey::ApprovalTiers);
+        env.storage().persistent().remove(&DataKey::Recovery);
+        let pending = Self::get_ids(&env, &DataKey::StatusIndex(TransactionStatus::Pending));
+        for tx_id in pending.iter() {
+            Self::fill_inboxes(&env, tx_id);
+        }
+
+        RecoveryCompleted {
+            signers: recovery.signers,
+            threshold: recovery.threshold,
+        }
+        .publish(&env);
+        Ok(())
+    }
+
+    /// Marks a pending or queued proposal whose lifetime has run out as
+    /// `Expired`. Anyone may call this; it only records what the ledger time
+    /// already implies.
+    pub fn expire_transaction(env: Env, tx_id: u64) -> Result<(), Error> {
+        Self::extend_instance(&env);
+        Self::when_migrated(&env)?;
+        let mut tx = Self::load_transaction(&env, tx_id)?;
+        if tx.status != TransactionStatus::Pending && tx.status != TransactionStatus::Queued {
+            return Err(Error::NotPending);
+        }
+        if !Self::is_expired(&env, &tx) {
+            return Err(Error::NotExpired);
+        }
+
+        Self::set_status(&env, &mut tx, TransactionStatus::Expired);
+
+        TransactionExpired { tx_id }.publish(&env);
+        Ok(())
+    }
+
+    /// Refuses a proposal lifetime no longer than the execution delay, as
+    /// such proposals would expire before their timelock elapsed. Zero
+    /// means proposals never expire.
+    fn check_lifetime(lifetime: u64, delay: u64) -> Result<(), Error> {
+        if lifetime > 0 && lifetime <= delay {
+            return Err(Error::TransactionExpired);
+        }
+        Ok(())
+    }
+
+    fn is_expired(env: &Env, tx: &Transaction) -> bool {
+        match tx.expires_at {
+            Some(expires_at) => env.ledger().timestamp() >= expires_at,
+            None => false,
+        }
+    }
+
+    fn self_execute(env: &Env, tx: &mut Transaction, executor: &Address) -> Result<(), Error> {
+        Self::set_status(env, tx, TransactionStatus::Executed);
+
+        // The status is written first so a re-entrant call cannot execute the
+        // same proposal twice.
+        for operation in tx.operations.iter() {
+            Self::perform(env, &operation)?;
+        }
+
+        TransactionExecuted {
+            tx_id: tx.id,
+            executor: executor.clone(),
+        }
+        .publish(env);
+        Ok(())
+    }
+
+    fn validate_operation(env: &Env, operation: &Operation) -> Result<(), Error> {
+        match operation {
+            Operation::Transfer(transfer) => {
+                if transfer.amount <= 0 {
+                    return Err(Error::InvalidAmount);
+                }
+                Self::check_recipient(env, &transfer.to)?;
+            }
+            Operation::Invoke(call) => {
+                if call.contract == env.current_contract_address() {
+                    return Err(Error::SelfInvocation);
+                }
+                Self::check_call_recipient(env, &call.function, &call.args)?;
+            }
+            // Signer-set changes are checked again on execution, since other
+            // proposals may have changed the set in the meantime.
+            Operation::AddSigner(signer, weight) => {
+                if env.storage().persistent().has(&DataKey::Signer(signer.clone())) {
+                    return Err(Error::SignerAlreadyExists);
+                }
+                if *weight == 0 {
+                    return Err(Error::InvalidWeight);
+                }
+            }
+            Operation::RemoveSigner(signer) => {
+                if !env.storage().persistent().has(&DataKey::Signer(signer.clone())) {
+                    return Err(Error::SignerNotFound);
+                }
+            }
+            Operation::SetSignerWeight(signer, weight) => {
+                if !env.storage().persistent().has(&DataKey::Signer(signer.clone())) {
+                    return Err(Error::SignerNotFound);
+                }
+                if *weight == 0 {
+                    return Err(Error::InvalidWeight);
+                }
+            }
+            Operation::UpdateThreshold(threshold) => {
+                if *threshold == 0 {
+                    return Err(Error::InvalidThreshold);
+                }
+            }
+            // Policy changes are only checked on execution, so that one batch
+            // can e.g. define a group and require it in the same proposal.
+            Operation::SetAdmin(_)
+            | Operation::SetGroup(_, _)
+            | Operation::SetGroupQuorums(_)
+            | Operation::SetApprovalTiers(_)
+            | Operation::SetSpendingLimit(_, _)
+            | Operation::RemoveSpendingLimit(_)
+            | Operation::RevokeAllowance(_, _)
+            | Operation::SetAllowlistEnabled(_)
+            | Operation::SetRecipientAllowed(_, _)
+            | Operation::SetRecipientDenied(_, _)
+            | Operation::SetGuardians(_)
+            | Operation::SetPauseThreshold(_)
+            | Operation::Upgrade(_) => {}
+            Operation::GrantAllowance(_, _, allowance) => {
+                if allowance.amount <= 0 {
+                    return Err(Error::InvalidAllowance);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn perform(env: &Env, operation: &Operation) -> Result<(), Error> {
+        match operation {
+            Operation::Transfer(transfer) => {
+                // The lists may have changed since the proposal was made.
+                Self::check_recipient(env, &transfer.to)?;
+                Self::record_spend(env, &transfer.token, transfer.amount)?;
+                token::Client::new(env, &transfer.token).transfer(
+                    &env.current_contract_address(),
+                    &transfer.to,
+                    &transfer.amount,
+                );
+            }
+            Operation::Invoke(call) => {
+                // Calling a limited token directly would move funds around
+                // its spending limit.
+                if env
+                    .storage()
+                    .persistent()
+                    .has(&DataKey::SpendingLimit(call.contract.clone()))
+                {
+                    return Err(Error::LimitedTokenInvocation);
+                }
+                Self::check_call_recipient(env, &call.function, &call.args)?;
+                // The vault is the direct invoker, so any `require_auth` on its
+                // address inside the target call is satisfied.
+                env.invoke_contract::<Val>(&call.contract, &call.function, call.args.clone());
+            }
+            Operation::AddSigner(signer, weight) => {
+                Self::apply_add_signer(env, signer.clone(), *weight)?
+            }
+            Operation::RemoveSigner(signer) => Self::apply_remove_signer(env, signer.clone())?,
+            Operation::SetSignerWeight(signer, weight) => {
+                Self::apply_set_signer_weight(env, signer.clone(), *weight)?
+            }
+            Operation::UpdateThreshold(threshold) => {
+                Self::apply_update_threshold(env, *threshold)?
+            }
+            Operation::SetAdmin(admin) => Self::apply_set_admin(env, admin.clone()),
+            Operation::SetGroup(group, members) => {
+                Self::apply_set_group(env, group.clone(), members.clone())?
+            }
+            Operation::SetGroupQuorums(quorums) => {
+                Self::apply_set_group_quorums(env, quorums.clone())?
+            }
+            Operation::SetApprovalTiers(tiers) => {
+                Self::apply_set_approval_tiers(env, tiers.clone())?
+            }
+            Operation::SetSpendingLimit(token, limit) => {
+                Self::apply_set_spending_limit(env, token.clone(), Some(limit.clone()))?
+            }
+            Operation::RemoveSpendingLimit(token) => {
+                Self::apply_set_spending_limit(env, token.clone(), None)?
+            }
+            Operation::GrantAllowance(signer, token, allowance) => {
+                Self::apply_grant_allowance(env, signer.clone(), token.clone(), allowance.clone())?
+            }
+            Operation::RevokeAllowance(signer, token) => {
+                Self::apply_revoke_allowance(env, signer.clone(), token.clone())?
+            }
+            Operation::SetAllowlistEnabled(enabled) => {
+                Self::apply_set_allowlist_enabled(env, *enabled)
+            }
+            Operation::SetRecipientAllowed(recipient, allowed) => {
+                Self::apply_set_recipient_allowed(env, recipient.clone(), *allowed)
+            }
+            Operation::SetRecipientDenied(recipient, denied) => {
+                Self::apply_set_recipient_denied(env, recipient.clone(), *denied)
+            }
+            Operation::SetGuardians(config) => Self::apply_set_guardians(env, config.clone())?,
+            Operation::SetPauseThreshold(threshold) => {
+                Self::apply_set_pause_threshold(env, *threshold)?
+            }
+            Operation::Upgrade(wasm_hash) => {
+                env.deployer()
+                    .update_current_contract_wasm(wasm_hash.clone());
+                Upgraded {
+                    wasm_hash: wasm_hash.clone(),
+                }
+                .publish(env);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_admin(env: &Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Admin)
+    }
+
+    pub fn get_threshold(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::Threshold)
+            .unwrap_or(0)
+    }
+
+    pub fn get_signers(env: &Env) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Signers)
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    fn update_signers_list(env: &Env, signer: &Address, is_add: bool) {
+        let mut signers: Vec<Address> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Signers)
+            .unwrap_or_else(|| Vec::new(env));
+
+        if is_add {
+            if !signers.contains(signer) {
+                signers.push_back(signer.clone());
+            }
+        } else if let Some(index) = signers.first_index_of(signer) {
+            signers.remove(index);
+        }
+
+        env.storage().persistent().set(&DataKey::Signers, &signers);
+    }
+
+    pub fn get_approvals(env: &Env, tx_id: u64) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Approvals(tx_id))
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    pub fn get_rejections(env: &Env, tx_id: u64) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Rejections(tx_id))
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    /// Weight of a signer, or zero for an address that is not a signer.
+    pub fn get_signer_weight(env: Env, signer: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Signer(signer))
+            .unwrap_or(0)
+    }
+
+    /// Combined current weight of `voters`; votes cast by since-removed
+    /// signers do not count towards the threshold.
+    fn vote_weight(env: &Env, voters: &Vec<Address>) -> u32 {
+        let mut weight = 0;
+        for voter in voters.iter() {
+            weight += Self::get_signer_weight(env.clone(), voter);
+        }
+        weight
+    }
+
+    fn total_weight(env: &Env) -> u32 {
+        Self::vote_weight(env, &Self::get_signers(env))
+    }
+
+    /// Number of current signers in `members` that also appear in `voters`.
+    fn count_members(env: &Env, members: &Vec<Address>, voters: &Vec<Address>) -> u32 {
+        let mut count = 0;
+        for member in members.iter() {
+            if voters.contains(&member)
+                && env.storage().persistent().has(&DataKey::Signer(member))
+            {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    pub fn get_groups(env: Env) -> Vec<Symbol> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Groups)
+            .unwrap_or_else(|| Vec::new(&env))
+    }
+
+    pub fn get_group(env: Env, group: Symbol) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Group(group))
+            .unwrap_or_else(|| Vec::new(&env))
+    }
+
+    pub fn get_spending_limit(env: Env, token: Address) -> Option<SpendingLimit> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::SpendingLimit(token))
+    }
+
+    pub fn get_spending_history(env: Env, token: Address) -> Option<SpendingHistory> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::SpendingHistory(token))
+    }
+
+    pub fn is_allowlist_enabled(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::AllowlistEnabled)
+            .unwrap_or(false)
+    }
+
+    /// Whether transfers to `recipient` are currently permitted.
+    pub fn is_recipient_permitted(env: Env, recipient: Address) -> bool {
+        Self::check_recipient(&env, &recipient).is_ok()
+    }
+
+    pub fn is_paused(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::Paused)
+            .unwrap_or(false)
+    }
+
+    pub fn get_pause_threshold(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::PauseThreshold)
+            .unwrap_or(1)
+    }
+
+    pub fn get_pause_votes(env: Env) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PauseVotes)
+            .unwrap_or_else(|| Vec::new(&env))
+    }
+
+    pub fn get_guardians(env: Env) -> Option<GuardianConfig> {
+        env.storage().persistent().get(&DataKey::Guardians)
+    }
+
+    pub fn get_recovery(env: Env) -> Option<Recovery> {
+        env.storage().persistent().get(&DataKey::Recovery)
+    }
+
+    pub fn get_allowance(env: Env, signer: Address, token: Address) -> Option<Allowance> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Allowance(signer, token))
+    }
+
+    pub fn get_approval_tiers(env: Env) -> Vec<ApprovalTier> {
+        env.storage()
+            .instance()
+            .get(&DataKey::ApprovalTiers)
+            .unwrap_or_else(|| Vec::new(&env))
+    }
+
+    pub fn get_group_quorums(env: Env) -> Vec<GroupQuorum> {
+        env.storage()
+            .instance()
+            .get(&DataKey::GroupQuorums)
+            .unwrap_or_else(|| Vec::new(&env))
+    }
+
+    /// Removes `voter` from the vote list at `key`, returning whether it was there.
+    fn remove_vote(env: &Env, key: &DataKey, voter: &Address) -> bool {
+        let mut votes: Vec<Address> = env
+            .storage()
+            .persistent()
+            .get(key)
+            .unwrap_or_else(|| Vec::new(env));
+
+        match votes.first_index_of(voter) {
+            Some(index) => {
+                votes.remove(index);
+                env.storage().persistent().set(key, &votes);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_transaction(env: Env, tx_id: u64) -> Option<Transaction> {
+        env.storage().persistent().get(&DataKey::Transaction(tx_id))
+    }
+
+    /// Returns up to `limit` transactions, capped at `MAX_PAGE_SIZE`, with
+    /// ids from `start` upwards, optionally only those in `status_filter`.
+    /// Pass one past the last returned id as the next `start`. Closed
+    /// statuses are not indexed, so filtering on one only looks at the ids
+    /// from `start` to `start + limit`; continue from there instead.
+    /// Proposals past their lifetime stay `Pending` until
+    /// `expire_transaction` runs.
+    pub fn list_transactions(
+        env: Env,
+        start: u64,
+        limit: u32,
+        status_filter: Option<TransactionStatus>,
+    ) -> Vec<Transaction> {
+        let limit = limit.min(MAX_PAGE_SIZE);
+        let mut page = Vec::new(&env);
+        let next_id: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::NextId)
+            .unwrap_or(1);
+
+        match status_filter {
+            Some(status) if Self::is_indexed(&status) => {
+                let ids = Self::get_ids(&env, &DataKey::StatusIndex(status));
+                let (Ok(from) | Err(from)) = ids.binary_search(start);
+                for tx_id in ids.slice(from..).iter() {
+                    if page.len() >= limit {
+                        break;
+                    }
+                    if let Some(tx) = Self::get_transaction(env.clone(), tx_id) {
+                        page.push_back(tx);
+                    }
+                }
+            }
+            Some(status) => {
+                let start = start.max(1);
+                let end = next_id.min(start.saturating_add(limit as u64));
+                for tx_id in start..end {
+                    if let Some(tx) = Self::get_transaction(env.clone(), tx_id) {
+                        if tx.status == status {
+                            page.push_back(tx);
+                        }
+                    }
+                }
+            }
+            None => {
+                let mut tx_id = start.max(1);
+                while tx_id < next_id && page.len() < limit {
+                    if let Some(tx) = Self::get_transaction(env.clone(), tx_id) {
+                        page.push_back(tx);
+                    }
+                    tx_id += 1;
+                }
+            }
+        }
+        page
+    }
+
+    /// Moves `tx` to `status`, keeping the status indexes and signer inboxes
+    /// in step, and stores it. Every status change goes through here.
+    fn set_status(env: &Env, tx: &mut Transaction, status: TransactionStatus) {
+        if Self::is_indexed(&tx.status) {
+            Self::remove_id(env, &DataKey::StatusIndex(tx.status.clone()), tx.id);
+        }
+        if Self::is_indexed(&status) {
+            Self::insert_id(env, &DataKey::StatusIndex(status.clone()), tx.id);
+        }
+        if tx.status == TransactionStatus::Pending && status != TransactionStatus::Pending {
+            Self::clear_inboxes(env, tx.id);
+        } else if tx.status != TransactionStatus::Pending && status == TransactionStatus::Pending {
+            Self::fill_inboxes(env, tx.id);
+        }
+        tx.status = status;
+        env.storage().persistent().set(&DataKey::Transaction(tx.id), tx);
+    }
+
+    /// Whether transactions in `status` are indexed. Only open statuses are,
+    /// so each index stays as small as the set of proposals in flight.
+    fn is_indexed(status: &TransactionStatus) -> bool {
+        matches!(status, TransactionStatus::Pending | TransactionStatus::Queued)
+    }
+
+    /// Ids of the pending transactions `signer` has neither approved nor
+    /// rejected, in ascending order. Proposals past their lifetime stay
+    /// listed until `expire_transaction` runs.
+    pub fn get_inbox(env: Env, signer: Address) -> Vec<u64> {
+        Self::get_ids(&env, &DataKey::Inbox(signer))
+    }
+
+    fn has_voted(env: &Env, tx_id: u64, signer: &Address) -> bool {
+        Self::get_approvals(env, tx_id).contains(signer)
+            || Self::get_rejections(env, tx_id).contains(signer)
+    }
+
+    /// Adds a pending transaction to the inbox of every signer yet to vote.
+    fn fill_inboxes(env: &Env, tx_id: u64) {
+        for signer in Self::get_signers(env).iter() {
+            if !Self::has_voted(env, tx_id, &signer) {
+                Self::insert_id(env, &DataKey::Inbox(signer), tx_id);
+            }
+        }
+    }
+
+    fn clear_inboxes(env: &Env, tx_id: u64) {
+        for signer in Self::get_signers(env).iter() {
+            Self::remove_id(env, &DataKey::Inbox(signer), tx_id);
+        }
+    }
+
+    /// Ascending transaction ids stored under an index key.
+    fn get_ids(env: &Env, key: &DataKey) -> Vec<u64> {
+        env.storage()
+            .persistent()
+            .get(key)
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    fn insert_id(env: &Env, key: &DataKey, tx_id: u64) {
+        let mut ids = Self::get_ids(env, key);
+        if let Err(index) = ids.binary_search(tx_id) {
+            ids.insert(index, tx_id);
+            env.storage().persistent().set(key, &ids);
+            Self::extend_persistent(env, key);
+        }
+    }
+
+    fn remove_id(env: &Env, key: &DataKey, tx_id: u64) {
+        let mut ids = Self::get_ids(env, key);
+        if let Ok(index) = ids.binary_search(tx_id) {
+            ids.remove(index);
+            env.storage().persistent().set(key, &ids);
+            Self::extend_persistent(env, key);
+        }
+    }
+
+    fn load_transaction(env: &Env, tx_id: u64) -> Result<Transaction, Error> {
+        let tx = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Transaction(tx_id))
+            .ok_or(Error::TransactionNotFound)?;
+        Self::extend_persistent(env, &DataKey::Transaction(tx_id));
+        Self::extend_persistent(env, &DataKey::Approvals(tx_id));
+        Self::extend_persistent(env, &DataKey::Rejections(tx_id));
+        Self::extend_persistent(env, &DataKey::VoteNonces(tx_id));
+        Ok(tx)
+    }
+
+    fn threshold(env: &Env) -> Result<u32, Error> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Threshold)
+            .ok_or(Error::NotInitialized)
+    }
+
+    // --- Storage TTL ---
+    /// Keeps the contract instance, and with it `Admin`, `Threshold` and
+    /// `NextId`, alive.
+    fn extend_instance(env: &Env) {
+        env.storage()
+            .instance()
+            .extend_ttl(TTL_THRESHOLD, TTL_EXTEND_TO);
+    }
+
+    fn extend_persistent(env: &Env, key: &DataKey) {
+        if env.storage().persistent().has(key) {
+            env.storage()
+                .persistent()
+                .extend_ttl(key, TTL_THRESHOLD, TTL_EXTEND_TO);
+        }
+    }
+
+    /// Extends the TTL of the instance, the signer set and the vault-wide
+    /// settings, plus any further persistent entries listed in `keys`, such
+    /// as old transactions or per-token limits. Anyone may call this.
+    pub fn bump(env: Env, keys: Vec<DataKey>) -> Result<(), Error> {
+        Self::threshold(&env)?;
+        Self::extend_instance(&env);
+
+        for key in [
+            DataKey::Signers,
+            DataKey::Groups,
+            DataKey::Guardians,
+            DataKey::Recovery,
+            DataKey::PauseVotes,
+            DataKey::UnpauseVotes,
+        ] {
+            Self::extend_persistent(&env, &key);
+        }
+        for signer in Self::get_signers(&env).iter() {
+            Self::extend_persistent(&env, &DataKey::Signer(signer.clone()));
+            Self::extend_persistent(&env, &DataKey::SignerKey(signer.clone()));
+            Self::extend_persistent(&env, &DataKey::AllowanceTokens(signer));
+        }
+        for group in Self::get_groups(env.clone()).iter() {
+            Self::extend_persistent(&env, &DataKey::Group(group));
+        }
+        for key in keys.iter() {
+            Self::extend_persistent(&env, &key);
+        }
+        Ok(())
+    }
+
+    fn self_approve(env: &Env, caller: &Address, tx_id: u64) {
+        let mut approvals: Vec<Address> = env
             .storage()
             .persistent()
             .get(&DataKey::Approvals(tx_id))
@@ -237,6 +3130,75 @@ impl MultiSigContract {
         if !found {
             approvals.push_back(caller.clone());
             env.storage().persistent().set(&DataKey::Approvals(tx_id), &approvals);
+            Self::remove_id(env, &DataKey::Inbox(caller.clone()), tx_id);
+
+            ApprovalCast {
+                tx_id,
+                signer: caller.clone(),
+            }
+            .publish(env);
+        }
+    }
+}
+
+/// Lets the vault's own address authorize calls, such as a protocol's
+/// `require_auth` on it, when its signers sign the authorization payload
+/// with their registered keys.
+#[contractimpl]
+impl CustomAccountInterface for MultiSigContract {
+    type Signature = Vec<(Address, SignerSignature)>;
+    type Error = Error;
+
+    /// Requires the same weight and group quorums as a proposal. Calls into
+    /// the vault itself or into spend-limited tokens are refused, as they
+    /// would sidestep its governance and limits, as are token calls paying
+    /// a recipient the lists forbid, and so is everything while an execution
+    /// delay is set, which direct authorization cannot honour.
+    fn __check_auth(
+        env: Env,
+        signature_payload: Hash<32>,
+        signatures: Self::Signature,
+        auth_contexts: Vec<Context>,
+    ) -> Result<(), Error> {
+        Self::when_migrated(&env)?;
+        Self::when_not_paused(&env)?;
+        let delay: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::ExecutionDelay)
+            .unwrap_or(0);
+        if delay > 0 {
+            return Err(Error::ExecutionDelayActive);
+        }
+
+        for context in auth_contexts.iter() {
+            if let Context::Contract(call) = context {
+                if call.contract == env.current_contract_address() {
+                    return Err(Error::SelfInvocation);
+                }
+                if env
+                    .storage()
+                    .persistent()
+                    .has(&DataKey::SpendingLimit(call.contract))
+                {
+                    return Err(Error::LimitedTokenInvocation);
+                }
+                Self::check_call_recipient(&env, &call.fn_name, &call.args)?;
+            }
+        }
+
+        let mut signers = Vec::new(&env);
+        for (signer, signature) in signatures.iter() {
+            Self::verify_signature(&env, &signer, &signature_payload, &signature)?;
+            if !signers.contains(&signer) {
+                signers.push_back(signer);
+            }
         }
+        if !Self::meets_quorum(&env, &signers, Self::threshold(&env)?) {
+            return Err(Error::InsufficientApprovals);
+        }
+        Ok(())
     }
 }
+
+mod test;
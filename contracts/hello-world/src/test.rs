@@ -1,21 +1,2840 @@
 #![cfg(test)]
+extern crate std;
 
 use super::*;
-use soroban_sdk::{vec, Env, String};
+use ed25519_dalek::{Signer as _, SigningKey};
+use p256::ecdsa::{signature::hazmat::PrehashSigner, Signature as P256Signature};
+use soroban_sdk::{
+    auth::ContractContext,
+    contract, contractimpl,
+    events::Event,
+    map, symbol_short,
+    testutils::{
+        storage::{Instance as _, Persistent as _},
+        Address as _, Events, Ledger, MuxedAddress as _,
+    },
+    token::StellarAssetClient,
+    vec, Bytes, BytesN, Env, IntoVal,
+};
+
+/// Stand-in for a contract governed by the vault: only its owner may set the value.
+#[contract]
+struct Governed;
+
+#[contractimpl]
+impl Governed {
+    pub fn set_value(env: Env, owner: Address, value: u32) {
+        owner.require_auth();
+        env.storage()
+            .instance()
+            .set(&symbol_short!("value"), &value);
+    }
+
+    pub fn value(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&symbol_short!("value"))
+            .unwrap_or(0)
+    }
+}
+
+struct Setup<'a> {
+    env: Env,
+    admin: Address,
+    signers: Vec<Address>,
+    client: MultiSigContractClient<'a>,
+    token: token::Client<'a>,
+}
+
+fn setup(signer_count: u32, threshold: u32) -> Setup<'static> {
+    setup_with_delay(signer_count, threshold, 0)
+}
+
+fn setup_with_delay(signer_count: u32, threshold: u32, execution_delay: u64) -> Setup<'static> {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let mut signers = Vec::new(&env);
+    for _ in 0..signer_count {
+        signers.push_back(Address::generate(&env));
+    }
+
+    let contract_id = env.register(MultiSigContract, ());
+    let client = MultiSigContractClient::new(&env, &contract_id);
+    client.initialize(
+        &Some(admin.clone()),
+        &signers,
+        &threshold,
+        &0,
+        &execution_delay,
+    );
+
+    let sac = env.register_stellar_asset_contract_v2(admin.clone());
+    StellarAssetClient::new(&env, &sac.address()).mint(&contract_id, &1_000);
+    let token = token::Client::new(&env, &sac.address());
+
+    Setup {
+        env,
+        admin,
+        signers,
+        client,
+        token,
+    }
+}
+
+#[test]
+fn test_initialize() {
+    let s = setup(3, 2);
+    assert_eq!(s.client.get_signers(), s.signers);
+    assert_eq!(s.client.get_transaction(&1), None);
+}
+
+#[test]
+fn test_transfer_executes_at_threshold() {
+    let s = setup(3, 2);
+    let recipient = Address::generate(&s.env);
+
+    let tx_id = s.client.propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &recipient,
+        &300,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_eq!(
+        s.client.get_approvals(&tx_id),
+        vec![&s.env, s.signers.get(0).unwrap()]
+    );
+    assert_eq!(s.token.balance(&recipient), 0);
+
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+    assert_eq!(s.token.balance(&recipient), 300);
+    assert_eq!(s.token.balance(&s.client.address), 700);
+}
+
+#[test]
+#[should_panic]
+fn test_execution_fails_with_insufficient_balance() {
+    let s = setup(2, 2);
+    let recipient = Address::generate(&s.env);
+
+    let tx_id = s.client.propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &recipient,
+        &5_000,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+}
+
+#[test]
+fn test_invocation_executes_at_threshold() {
+    let s = setup(3, 2);
+    let governed_id = s.env.register(Governed, ());
+    let governed = GovernedClient::new(&s.env, &governed_id);
+
+    let args: Vec<Val> = vec![
+        &s.env,
+        s.client.address.into_val(&s.env),
+        42u32.into_val(&s.env),
+    ];
+    let tx_id = s.client.propose_invocation(
+        &s.signers.get(0).unwrap(),
+        &governed_id,
+        &symbol_short!("set_value"),
+        &args,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_eq!(governed.value(), 0);
+
+    s.client
+        .approve_transaction(&s.signers.get(2).unwrap(), &tx_id);
+
+    assert_eq!(governed.value(), 42);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+    assert_eq!(
+        tx.operations,
+        vec![
+            &s.env,
+            Operation::Invoke(Invocation {
+                contract: governed_id,
+                function: symbol_short!("set_value"),
+                args,
+            })
+        ]
+    );
+}
+
+fn transfer(s: &Setup, to: &Address, amount: i128) -> Operation {
+    Operation::Transfer(Transfer {
+        token: s.token.address.clone(),
+        to: to.clone(),
+        amount,
+    })
+}
+
+#[test]
+fn test_batch_executes_all_operations() {
+    let s = setup(2, 2);
+    let alice = Address::generate(&s.env);
+    let bob = Address::generate(&s.env);
+
+    let operations = vec![
+        &s.env,
+        transfer(&s, &alice, 100),
+        transfer(&s, &bob, 250),
+        transfer(&s, &alice, 50),
+    ];
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &operations,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+
+    assert_eq!(s.token.balance(&alice), 150);
+    assert_eq!(s.token.balance(&bob), 250);
+    assert_eq!(s.token.balance(&s.client.address), 600);
+}
+
+#[test]
+fn test_batch_reverts_when_any_operation_fails() {
+    let s = setup(2, 2);
+    let alice = Address::generate(&s.env);
+    let bob = Address::generate(&s.env);
+
+    let operations = vec![&s.env, transfer(&s, &alice, 600), transfer(&s, &bob, 600)];
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &operations,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert!(result.is_err());
+
+    assert_eq!(s.token.balance(&alice), 0);
+    assert_eq!(s.token.balance(&s.client.address), 1_000);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+}
+
+fn propose_payment(s: &Setup, proposer: u32, amount: i128) -> u64 {
+    s.client.propose_transaction(
+        &s.signers.get(proposer).unwrap(),
+        &s.token.address,
+        &Address::generate(&s.env),
+        &amount,
+        &Bytes::new(&s.env),
+        &None,
+    )
+}
+
+#[test]
+fn test_rejected_once_threshold_unreachable() {
+    let s = setup(4, 3);
+    let tx_id = propose_payment(&s, 0, 100);
+
+    s.client
+        .reject_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+
+    // The proposer switching sides leaves only two possible approvals.
+    s.client
+        .reject_transaction(&s.signers.get(0).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Rejected);
+    assert_eq!(s.client.get_approvals(&tx_id).len(), 0);
+    assert_eq!(s.client.get_rejections(&tx_id).len(), 2);
+
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(2).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotPending)));
+}
+
+#[test]
+fn test_approve_after_reject_switches_vote() {
+    let s = setup(3, 2);
+    let tx_id = propose_payment(&s, 0, 100);
+
+    s.client
+        .reject_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+
+    assert_eq!(s.client.get_rejections(&tx_id).len(), 0);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+}
+
+#[test]
+fn test_cancel_by_proposer_or_admin() {
+    let s = setup(3, 2);
+
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client
+        .cancel_transaction(&s.signers.get(0).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Cancelled);
+
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client.cancel_transaction(&s.admin, &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Cancelled);
+
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotPending)));
+}
+
+#[test]
+fn test_cancel_by_other_signer_fails() {
+    let s = setup(3, 2);
+    let tx_id = propose_payment(&s, 0, 100);
+
+    let result = s
+        .client
+        .try_cancel_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotProposerOrAdmin)));
+}
+
+#[test]
+fn test_initialize_errors() {
+    let s = setup(3, 2);
+    let result = s
+        .client
+        .try_initialize(&Some(s.admin.clone()), &s.signers, &2, &0, &0);
+    assert_eq!(result, Err(Ok(Error::AlreadyInitialized)));
+
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = MultiSigContractClient::new(&env, &env.register(MultiSigContract, ()));
+    let admin = Address::generate(&env);
+    let signers = vec![&env, Address::generate(&env), Address::generate(&env)];
+
+    let result = client.try_initialize(&Some(admin.clone()), &Vec::new(&env), &1, &0, &0);
+    assert_eq!(result, Err(Ok(Error::NoSigners)));
+    let result = client.try_initialize(&Some(admin.clone()), &signers, &0, &0, &0);
+    assert_eq!(result, Err(Ok(Error::InvalidThreshold)));
+    let result = client.try_initialize(&Some(admin.clone()), &signers, &3, &0, &0);
+    assert_eq!(result, Err(Ok(Error::InvalidThreshold)));
+}
+
+#[test]
+fn test_proposal_errors() {
+    let s = setup(3, 2);
+    let outsider = Address::generate(&s.env);
+    let data = Bytes::new(&s.env);
+
+    let result = s.client.try_propose_transaction(
+        &outsider,
+        &s.token.address,
+        &outsider,
+        &100,
+        &data,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::NotSigner)));
+
+    let signer = s.signers.get(0).unwrap();
+    let result =
+        s.client
+            .try_propose_transaction(&signer, &s.token.address, &outsider, &0, &data, &None);
+    assert_eq!(result, Err(Ok(Error::InvalidAmount)));
+
+    let result = s
+        .client
+        .try_propose_batch(&signer, &Vec::new(&s.env), &data, &None);
+    assert_eq!(result, Err(Ok(Error::NoOperations)));
+
+    let result = s.client.try_propose_invocation(
+        &signer,
+        &s.client.address,
+        &Symbol::new(&s.env, "add_signer"),
+        &Vec::new(&s.env),
+        &data,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::SelfInvocation)));
+
+    let result = s.client.try_approve_transaction(&signer, &99);
+    assert_eq!(result, Err(Ok(Error::TransactionNotFound)));
+}
+
+#[test]
+fn test_admin_errors() {
+    let s = setup(3, 3);
+    let signer = s.signers.get(0).unwrap();
+
+    let result = s
+        .client
+        .try_add_signer(&signer, &Address::generate(&s.env), &1);
+    assert_eq!(result, Err(Ok(Error::NotAdmin)));
+    let result = s.client.try_add_signer(&s.admin, &signer, &1);
+    assert_eq!(result, Err(Ok(Error::SignerAlreadyExists)));
+    let result = s.client.try_remove_signer(&s.admin, &signer);
+    assert_eq!(result, Err(Ok(Error::BelowThreshold)));
+    let result = s.client.try_update_threshold(&s.admin, &4);
+    assert_eq!(result, Err(Ok(Error::InvalidThreshold)));
+}
+
+/// Asserts that the vault published `expected` during the last invocation.
+fn assert_published(s: &Setup, expected: impl Event) {
+    let topics = expected.topics(&s.env);
+    let data = expected.data(&s.env);
+    let found = s.env.events().all().iter().any(|(contract, t, d)| {
+        contract == s.client.address && t == topics && vec![&s.env, d] == vec![&s.env, data]
+    });
+    assert!(found, "event not published");
+}
+
+#[test]
+fn test_events_for_proposal_lifecycle() {
+    let s = setup(3, 2);
+    let signer_a = s.signers.get(0).unwrap();
+    let signer_b = s.signers.get(1).unwrap();
+    let recipient = Address::generate(&s.env);
+
+    let tx_id = s.client.propose_transaction(
+        &signer_a,
+        &s.token.address,
+        &recipient,
+        &100,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_published(
+        &s,
+        TransactionProposed {
+            tx_id,
+            proposer: signer_a.clone(),
+            operations: vec![&s.env, transfer(&s, &recipient, 100)],
+        },
+    );
+    assert_published(
+        &s,
+        ApprovalCast {
+            tx_id,
+            signer: signer_a.clone(),
+        },
+    );
+
+    s.client.approve_transaction(&signer_b, &tx_id);
+    assert_published(
+        &s,
+        ApprovalCast {
+            tx_id,
+            signer: signer_b.clone(),
+        },
+    );
+    assert_published(
+        &s,
+        TransactionExecuted {
+            tx_id,
+            executor: signer_b,
+        },
+    );
+
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client.reject_transaction(&signer_a, &tx_id);
+    assert_published(
+        &s,
+        ApprovalRevoked {
+            tx_id,
+            signer: signer_a.clone(),
+        },
+    );
+    assert_published(
+        &s,
+        RejectionCast {
+            tx_id,
+            signer: signer_a.clone(),
+        },
+    );
+
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client.cancel_transaction(&signer_a, &tx_id);
+    assert_published(
+        &s,
+        TransactionCancelled {
+            tx_id,
+            caller: signer_a,
+        },
+    );
+}
+
+#[test]
+fn test_events_for_signer_changes() {
+    let s = setup(3, 2);
+    let new_signer = Address::generate(&s.env);
+
+    s.client.add_signer(&s.admin, &new_signer, &2);
+    assert_published(
+        &s,
+        SignerAdded {
+            signer: new_signer.clone(),
+            weight: 2,
+        },
+    );
+
+    s.client.update_threshold(&s.admin, &3);
+    assert_published(&s, ThresholdUpdated { threshold: 3 });
+
+    s.client.remove_signer(&s.admin, &new_signer);
+    assert_published(&s, SignerRemoved { signer: new_signer });
+}
+
+#[test]
+fn test_expired_proposal_cannot_be_approved() {
+    let s = setup(3, 2);
+    let tx_id = s.client.propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &Address::generate(&s.env),
+        &100,
+        &Bytes::new(&s.env),
+        &Some(3_600),
+    );
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.expires_at, Some(tx.created_at + 3_600));
+
+    let result = s.client.try_expire_transaction(&tx_id);
+    assert_eq!(result, Err(Ok(Error::NotExpired)));
+
+    s.env.ledger().with_mut(|l| l.timestamp += 3_600);
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::TransactionExpired)));
+    assert_eq!(s.token.balance(&s.client.address), 1_000);
+
+    s.client.expire_transaction(&tx_id);
+    assert_published(&s, TransactionExpired { tx_id });
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Expired);
+
+    let result = s.client.try_expire_transaction(&tx_id);
+    assert_eq!(result, Err(Ok(Error::NotPending)));
+}
+
+#[test]
+fn test_default_proposal_lifetime() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = MultiSigContractClient::new(&env, &env.register(MultiSigContract, ()));
+    let signer = Address::generate(&env);
+    client.initialize(&None, &vec![&env, signer.clone()], &1, &600, &0);
+
+    let args = Vec::new(&env);
+    let target = env.register(Governed, ());
+    let function = symbol_short!("value");
+    let data = Bytes::new(&env);
+
+    let tx_id = client.propose_invocation(&signer, &target, &function, &args, &data, &None);
+    let tx = client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.expires_at, Some(tx.created_at + 600));
+
+    let tx_id = client.propose_invocation(&signer, &target, &function, &args, &data, &Some(0));
+    let tx = client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.expires_at, None);
+}
+
+#[test]
+fn test_timelock_queues_then_executes() {
+    let s = setup_with_delay(3, 2, 86_400);
+    let signer = s.signers.get(1).unwrap();
+    let recipient = Address::generate(&s.env);
+
+    let tx_id = s.client.propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &recipient,
+        &100,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client.approve_transaction(&signer, &tx_id);
+    let eta = s.env.ledger().timestamp() + 86_400;
+    assert_published(&s, TransactionQueued { tx_id, eta });
+
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Queued);
+    assert_eq!(tx.eta, Some(eta));
+    assert_eq!(s.token.balance(&recipient), 0);
+
+    let result = s.client.try_execute_transaction(&signer, &tx_id);
+    assert_eq!(result, Err(Ok(Error::TimelockNotElapsed)));
+
+    s.env.ledger().with_mut(|l| l.timestamp = eta);
+    s.client.execute_transaction(&signer, &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+    assert_eq!(s.token.balance(&recipient), 100);
+
+    let result = s.client.try_execute_transaction(&signer, &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotQueued)));
+}
+
+#[test]
+fn test_queued_proposal_can_be_stopped() {
+    let s = setup_with_delay(3, 2, 86_400);
+
+    // A rejection during the delay drops the proposal back below threshold.
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    s.client
+        .reject_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+    assert_eq!(tx.eta, None);
+
+    // The proposer can withdraw a queued proposal outright.
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    s.client
+        .cancel_transaction(&s.signers.get(0).unwrap(), &tx_id);
+    s.env.ledger().with_mut(|l| l.timestamp += 86_400);
+    let result = s
+        .client
+        .try_execute_transaction(&s.signers.get(2).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotQueued)));
+    assert_eq!(s.token.balance(&s.client.address), 1_000);
+}
+
+#[test]
+fn test_signer_vetoes_queued_payout() {
+    let s = setup_with_delay(3, 2, 86_400);
+    let bystander = s.signers.get(2).unwrap();
+    let tx_id = propose_payment(&s, 0, 100);
+
+    // Only the proposer or admin may withdraw a proposal still collecting votes.
+    let result = s.client.try_cancel_transaction(&bystander, &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotProposerOrAdmin)));
+
+    // Two keys queue the payout; the signer who did not approve vetoes it.
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    s.client.cancel_transaction(&bystander, &tx_id);
+    assert_published(
+        &s,
+        TransactionCancelled {
+            tx_id,
+            caller: bystander.clone(),
+        },
+    );
+
+    s.env.ledger().with_mut(|l| l.timestamp += 86_400);
+    let result = s
+        .client
+        .try_execute_transaction(&s.signers.get(0).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotQueued)));
+    assert_eq!(s.token.balance(&s.client.address), 1_000);
+}
+
+#[test]
+fn test_queued_proposal_expires() {
+    let s = setup_with_delay(2, 2, 3_600);
+    let signer = s.signers.get(1).unwrap();
+    let tx_id = s.client.propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &Address::generate(&s.env),
+        &100,
+        &Bytes::new(&s.env),
+        &Some(7_200),
+    );
+    s.client.approve_transaction(&signer, &tx_id);
+
+    s.env.ledger().with_mut(|l| l.timestamp += 7_200);
+    let result = s.client.try_execute_transaction(&signer, &tx_id);
+    assert_eq!(result, Err(Ok(Error::TransactionExpired)));
+
+    s.client.expire_transaction(&tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Expired);
+    assert_eq!(s.token.balance(&s.client.address), 1_000);
+}
 
 #[test]
-fn test() {
+fn test_lifetime_must_outlast_delay() {
+    let s = setup_with_delay(2, 2, 3_600);
+    let result = s.client.try_propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &Address::generate(&s.env),
+        &100,
+        &Bytes::new(&s.env),
+        &Some(3_600),
+    );
+    assert_eq!(result, Err(Ok(Error::TransactionExpired)));
+
+    // An approval too late for the timelock to elapse in time cannot queue.
+    let tx_id = s.client.propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &Address::generate(&s.env),
+        &100,
+        &Bytes::new(&s.env),
+        &Some(7_200),
+    );
+    s.env.ledger().with_mut(|l| l.timestamp += 3_600);
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::TransactionExpired)));
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+
     let env = Env::default();
-    let contract_id = env.register(Contract, ());
-    let client = ContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    let client = MultiSigContractClient::new(&env, &env.register(MultiSigContract, ()));
+    let signers = vec![&env, Address::generate(&env)];
+    let result = client.try_initialize(&None, &signers, &1, &600, &600);
+    assert_eq!(result, Err(Ok(Error::TransactionExpired)));
+    client.initialize(&None, &signers, &1, &601, &600);
+}
+
+#[test]
+fn test_revoke_approval() {
+    let s = setup(3, 3);
+    let signer = s.signers.get(1).unwrap();
+    let tx_id = propose_payment(&s, 0, 100);
+
+    let result = s.client.try_revoke_approval(&signer, &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotApproved)));
+
+    s.client.approve_transaction(&signer, &tx_id);
+    s.client.revoke_approval(&signer, &tx_id);
+    assert_published(
+        &s,
+        ApprovalRevoked {
+            tx_id,
+            signer: signer.clone(),
+        },
+    );
+    assert_eq!(
+        s.client.get_approvals(&tx_id),
+        vec![&s.env, s.signers.get(0).unwrap()]
+    );
+
+    // The revoked approval no longer counts towards the threshold.
+    s.client
+        .approve_transaction(&s.signers.get(2).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+}
+
+#[test]
+fn test_revoke_approval_dequeues() {
+    let s = setup_with_delay(3, 2, 3_600);
+    let signer = s.signers.get(1).unwrap();
+    let tx_id = propose_payment(&s, 0, 100);
+
+    s.client.approve_transaction(&signer, &tx_id);
+    s.client.revoke_approval(&signer, &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+    assert_eq!(tx.eta, None);
+
+    s.env.ledger().with_mut(|l| l.timestamp += 3_600);
+    let result = s.client.try_execute_transaction(&signer, &tx_id);
+    assert_eq!(result, Err(Ok(Error::NotQueued)));
+}
+
+#[test]
+fn test_governance_through_proposals() {
+    let s = setup(3, 2);
+    let new_signer = Address::generate(&s.env);
+    let old_signer = s.signers.get(2).unwrap();
+
+    let operations = vec![
+        &s.env,
+        Operation::AddSigner(new_signer.clone(), 1),
+        Operation::RemoveSigner(old_signer.clone()),
+        Operation::UpdateThreshold(3),
+        Operation::SetAdmin(None),
+    ];
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &operations,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_published(&s, AdminUpdated { admin: None });
 
-    let words = client.hello(&String::from_str(&env, "Dev"));
     assert_eq!(
-        words,
+        s.client.get_signers(),
         vec![
-            &env,
-            String::from_str(&env, "Hello"),
-            String::from_str(&env, "Dev"),
+            &s.env,
+            s.signers.get(0).unwrap(),
+            s.signers.get(1).unwrap(),
+            new_signer.clone(),
         ]
     );
+    assert_eq!(s.client.get_threshold(), 3);
+    assert_eq!(s.client.get_admin(), None);
+
+    let result = s.client.try_add_signer(&s.admin, &old_signer, &1);
+    assert_eq!(result, Err(Ok(Error::AdminDisabled)));
+}
+
+#[test]
+fn test_invalid_governance_change_reverts_execution() {
+    let s = setup(3, 2);
+    let operations = vec![&s.env, Operation::UpdateThreshold(4)];
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &operations,
+        &Bytes::new(&s.env),
+        &None,
+    );
+
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::InvalidThreshold)));
+    assert_eq!(s.client.get_threshold(), 2);
+}
+
+#[test]
+fn test_weighted_threshold() {
+    // Three signers of weight one need a total weight of three.
+    let s = setup(3, 3);
+    let cfo = s.signers.get(0).unwrap();
+    s.client.set_signer_weight(&s.admin, &cfo, &2);
+    assert_published(
+        &s,
+        SignerWeightUpdated {
+            signer: cfo.clone(),
+            weight: 2,
+        },
+    );
+    assert_eq!(s.client.get_signer_weight(&cfo), 2);
+
+    // The CFO plus any one other signer now meets the threshold.
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client
+        .approve_transaction(&s.signers.get(2).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+
+    // Two weight-one signers alone do not.
+    let tx_id = propose_payment(&s, 1, 100);
+    s.client
+        .approve_transaction(&s.signers.get(2).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+
+    // Rejection by the CFO leaves a total weight of two, below the threshold.
+    s.client.reject_transaction(&cfo, &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Rejected);
+}
+
+#[test]
+fn test_weight_changes_respect_threshold() {
+    let s = setup(3, 3);
+    let signer = s.signers.get(0).unwrap();
+
+    let result = s.client.try_set_signer_weight(&s.admin, &signer, &0);
+    assert_eq!(result, Err(Ok(Error::InvalidWeight)));
+    let result = s
+        .client
+        .try_set_signer_weight(&s.admin, &Address::generate(&s.env), &1);
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+
+    s.client.set_signer_weight(&s.admin, &signer, &3);
+    s.client.update_threshold(&s.admin, &5);
+    let result = s.client.try_set_signer_weight(&s.admin, &signer, &2);
+    assert_eq!(result, Err(Ok(Error::BelowThreshold)));
+    let result = s
+        .client
+        .try_remove_signer(&s.admin, &s.signers.get(1).unwrap());
+    assert_eq!(result, Err(Ok(Error::BelowThreshold)));
+
+    // The combined weight must fit in a u32.
+    let result = s.client.try_set_signer_weight(&s.admin, &signer, &u32::MAX);
+    assert_eq!(result, Err(Ok(Error::InvalidWeight)));
+    let result = s
+        .client
+        .try_add_signer(&s.admin, &Address::generate(&s.env), &(u32::MAX - 4));
+    assert_eq!(result, Err(Ok(Error::InvalidWeight)));
+    s.client
+        .add_signer(&s.admin, &Address::generate(&s.env), &(u32::MAX - 5));
+    assert_eq!(s.client.get_signers().len(), 4);
+}
+
+#[test]
+fn test_group_quorums_required() {
+    let s = setup(4, 2);
+    let finance = symbol_short!("finance");
+    let security = symbol_short!("security");
+    let finance_members = vec![&s.env, s.signers.get(0).unwrap(), s.signers.get(1).unwrap()];
+    let security_members = vec![&s.env, s.signers.get(2).unwrap()];
+
+    // Defining the groups and their quorums can be a single proposal.
+    let operations = vec![
+        &s.env,
+        Operation::SetGroup(finance.clone(), finance_members.clone()),
+        Operation::SetGroup(security.clone(), security_members),
+        Operation::SetGroupQuorums(vec![
+            &s.env,
+            GroupQuorum {
+                group: finance.clone(),
+                min_approvals: 2,
+            },
+            GroupQuorum {
+                group: security.clone(),
+                min_approvals: 1,
+            },
+        ]),
+    ];
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &operations,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client
+        .approve_transaction(&s.signers.get(3).unwrap(), &tx_id);
+    assert_eq!(
+        s.client.get_groups(),
+        vec![&s.env, finance.clone(), security]
+    );
+    assert_eq!(s.client.get_group(&finance), finance_members);
+
+    // Both finance signers meet the weight threshold but not the security quorum.
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+
+    s.client
+        .approve_transaction(&s.signers.get(2).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+
+    // A single finance rejection makes the finance quorum unreachable.
+    let tx_id = propose_payment(&s, 3, 100);
+    s.client
+        .reject_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Rejected);
+}
+
+#[test]
+fn test_group_validation() {
+    let s = setup(3, 2);
+    let finance = symbol_short!("finance");
+    let quorum = GroupQuorum {
+        group: finance.clone(),
+        min_approvals: 2,
+    };
+
+    let result = s
+        .client
+        .try_set_group_quorums(&s.admin, &vec![&s.env, quorum.clone()]);
+    assert_eq!(result, Err(Ok(Error::GroupNotFound)));
+
+    let outsider = Address::generate(&s.env);
+    let result = s
+        .client
+        .try_set_group(&s.admin, &finance, &vec![&s.env, outsider]);
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+
+    let twice = vec![&s.env, s.signers.get(0).unwrap(), s.signers.get(0).unwrap()];
+    let result = s.client.try_set_group(&s.admin, &finance, &twice);
+    assert_eq!(result, Err(Ok(Error::SignerAlreadyExists)));
+
+    let members = vec![&s.env, s.signers.get(0).unwrap()];
+    s.client.set_group(&s.admin, &finance, &members);
+    let result = s
+        .client
+        .try_set_group_quorums(&s.admin, &vec![&s.env, quorum.clone()]);
+    assert_eq!(result, Err(Ok(Error::InvalidQuorum)));
+
+    let members = vec![&s.env, s.signers.get(0).unwrap(), s.signers.get(1).unwrap()];
+    s.client.set_group(&s.admin, &finance, &members);
+    s.client.set_group_quorums(&s.admin, &vec![&s.env, quorum]);
+    let result = s
+        .client
+        .try_set_group(&s.admin, &finance, &Vec::new(&s.env));
+    assert_eq!(result, Err(Ok(Error::GroupInUse)));
+
+    // Removing a member would leave the quorum unreachable.
+    let result = s
+        .client
+        .try_remove_signer(&s.admin, &s.signers.get(1).unwrap());
+    assert_eq!(result, Err(Ok(Error::GroupInUse)));
+    s.client.remove_signer(&s.admin, &s.signers.get(2).unwrap());
+}
+
+#[test]
+fn test_approval_tiers() {
+    let s = setup(4, 4);
+    let tiers = vec![
+        &s.env,
+        ApprovalTier {
+            max_amount: 100,
+            threshold: 1,
+        },
+        ApprovalTier {
+            max_amount: 500,
+            threshold: 2,
+        },
+    ];
+    s.client.set_approval_tiers(&s.admin, &tiers);
+    assert_eq!(s.client.get_approval_tiers(), tiers);
+
+    // Small payments only need the proposer.
+    let tx_id = propose_payment(&s, 0, 100);
+    s.client
+        .approve_transaction(&s.signers.get(0).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+
+    // Mid-sized payments need two approvals.
+    let tx_id = propose_payment(&s, 0, 500);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+
+    // Above the last tier the global threshold applies.
+    let tx_id = propose_payment(&s, 0, 501);
+    for i in 1..3 {
+        s.client
+            .approve_transaction(&s.signers.get(i).unwrap(), &tx_id);
+    }
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+
+    // So it does to small payments in more than one token.
+    let other = s.env.register_stellar_asset_contract_v2(s.admin.clone());
+    let to = Address::generate(&s.env);
+    let operations = vec![
+        &s.env,
+        transfer(&s, &to, 10),
+        Operation::Transfer(Transfer {
+            token: other.address(),
+            to,
+            amount: 10,
+        }),
+    ];
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &operations,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    for i in 0..3 {
+        s.client
+            .approve_transaction(&s.signers.get(i).unwrap(), &tx_id);
+    }
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Pending);
+}
+
+#[test]
+fn test_approval_tiers_validation() {
+    let s = setup(3, 2);
+    let tier = |max_amount: i128, threshold: u32| ApprovalTier {
+        max_amount,
+        threshold,
+    };
+
+    let unordered = vec![&s.env, tier(500, 2), tier(100, 1)];
+    let result = s.client.try_set_approval_tiers(&s.admin, &unordered);
+    assert_eq!(result, Err(Ok(Error::InvalidTiers)));
+
+    let unreachable = vec![&s.env, tier(100, 4)];
+    let result = s.client.try_set_approval_tiers(&s.admin, &unreachable);
+    assert_eq!(result, Err(Ok(Error::InvalidTiers)));
+
+    let zero = vec![&s.env, tier(100, 0)];
+    let result = s.client.try_set_approval_tiers(&s.admin, &zero);
+    assert_eq!(result, Err(Ok(Error::InvalidTiers)));
+
+    // Signer changes may not leave a tier unreachable either.
+    s.client
+        .set_approval_tiers(&s.admin, &vec![&s.env, tier(100, 3)]);
+    let signer = s.signers.get(0).unwrap();
+    let result = s.client.try_remove_signer(&s.admin, &signer);
+    assert_eq!(result, Err(Ok(Error::BelowThreshold)));
+    s.client.set_signer_weight(&s.admin, &signer, &2);
+    s.client.remove_signer(&s.admin, &s.signers.get(1).unwrap());
+}
+
+#[test]
+fn test_spending_limit_per_window() {
+    let s = setup(2, 2);
+    let limit = SpendingLimit {
+        amount: 300,
+        window: 86_400,
+    };
+    s.client
+        .set_spending_limit(&s.admin, &s.token.address, &limit);
+    assert_eq!(s.client.get_spending_limit(&s.token.address), Some(limit));
+
+    let tx_id = propose_payment(&s, 0, 200);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    let bucket = s.env.ledger().timestamp() / 3_600;
+    assert_eq!(
+        s.client.get_spending_history(&s.token.address),
+        Some(SpendingHistory {
+            buckets: map![&s.env, (bucket, 200)],
+        })
+    );
+
+    // The threshold is met, but the daily limit is not.
+    let tx_id = propose_payment(&s, 0, 200);
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::SpendingLimitExceeded)));
+
+    s.env.ledger().with_mut(|l| l.timestamp += 86_400 + 3_600);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(s.token.balance(&s.client.address), 600);
+}
+
+#[test]
+fn test_spending_limit_rolls() {
+    let s = setup(2, 2);
+    let limit = SpendingLimit {
+        amount: 300,
+        window: 86_400,
+    };
+    s.client
+        .set_spending_limit(&s.admin, &s.token.address, &limit);
+    let pay = |amount: i128| {
+        let tx_id = propose_payment(&s, 0, amount);
+        s.client
+            .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id)
+    };
+
+    assert!(pay(100).is_ok());
+    s.env.ledger().with_mut(|l| l.timestamp += 86_399);
+    assert!(pay(200).is_ok());
+
+    // Spends do not reset at a window boundary: the last day still counts.
+    s.env.ledger().with_mut(|l| l.timestamp += 1);
+    assert_eq!(pay(200), Err(Ok(Error::SpendingLimitExceeded)));
+    s.env.ledger().with_mut(|l| l.timestamp += 3_600);
+    assert!(pay(100).is_ok());
+    assert_eq!(pay(1), Err(Ok(Error::SpendingLimitExceeded)));
+    assert_eq!(s.token.balance(&s.client.address), 600);
+}
+
+#[test]
+fn test_spending_limit_blocks_direct_token_calls() {
+    let s = setup(2, 2);
+    let limit = SpendingLimit {
+        amount: 300,
+        window: 86_400,
+    };
+    s.client
+        .set_spending_limit(&s.admin, &s.token.address, &limit);
+
+    let args: Vec<Val> = vec![
+        &s.env,
+        s.client.address.into_val(&s.env),
+        Address::generate(&s.env).into_val(&s.env),
+        1_000i128.into_val(&s.env),
+    ];
+    let tx_id = s.client.propose_invocation(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &Symbol::new(&s.env, "transfer"),
+        &args,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::LimitedTokenInvocation)));
+
+    let invalid = SpendingLimit {
+        amount: 0,
+        window: 86_400,
+    };
+    let result = s
+        .client
+        .try_set_spending_limit(&s.admin, &s.token.address, &invalid);
+    assert_eq!(result, Err(Ok(Error::InvalidSpendingLimit)));
+}
+
+#[test]
+fn test_spend_allowance() {
+    let s = setup(3, 2);
+    let ops_engineer = s.signers.get(2).unwrap();
+    let vendor = Address::generate(&s.env);
+    let expires_at = s.env.ledger().timestamp() + 86_400;
+
+    let operations = vec![
+        &s.env,
+        Operation::GrantAllowance(
+            ops_engineer.clone(),
+            s.token.address.clone(),
+            Allowance {
+                amount: 150,
+                expires_at,
+            },
+        ),
+    ];
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &operations,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+
+    s.client
+        .spend_allowance(&ops_engineer, &s.token.address, &vendor, &100);
+    assert_published(
+        &s,
+        AllowanceSpent {
+            signer: ops_engineer.clone(),
+            token: s.token.address.clone(),
+            to: vendor.clone(),
+            amount: 100,
+        },
+    );
+    assert_eq!(s.token.balance(&vendor), 100);
+    assert_eq!(
+        s.client.get_allowance(&ops_engineer, &s.token.address),
+        Some(Allowance {
+            amount: 50,
+            expires_at
+        })
+    );
+
+    let result = s
+        .client
+        .try_spend_allowance(&ops_engineer, &s.token.address, &vendor, &51);
+    assert_eq!(result, Err(Ok(Error::AllowanceExceeded)));
+
+    s.env.ledger().with_mut(|l| l.timestamp = expires_at);
+    let result = s
+        .client
+        .try_spend_allowance(&ops_engineer, &s.token.address, &vendor, &50);
+    assert_eq!(result, Err(Ok(Error::AllowanceExpired)));
+
+    let other = s.signers.get(0).unwrap();
+    let result = s
+        .client
+        .try_spend_allowance(&other, &s.token.address, &vendor, &1);
+    assert_eq!(result, Err(Ok(Error::AllowanceNotFound)));
+}
+
+#[test]
+fn test_spend_allowance_respects_spending_limit() {
+    let s = setup(2, 2);
+    let signer = s.signers.get(0).unwrap();
+    let limit = SpendingLimit {
+        amount: 100,
+        window: 86_400,
+    };
+    let allowance = Allowance {
+        amount: 500,
+        expires_at: s.env.ledger().timestamp() + 86_400,
+    };
+    let operations = vec![
+        &s.env,
+        Operation::SetSpendingLimit(s.token.address.clone(), limit),
+        Operation::GrantAllowance(signer.clone(), s.token.address.clone(), allowance),
+    ];
+    let tx_id = s
+        .client
+        .propose_batch(&signer, &operations, &Bytes::new(&s.env), &None);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+
+    let vendor = Address::generate(&s.env);
+    let result = s
+        .client
+        .try_spend_allowance(&signer, &s.token.address, &vendor, &101);
+    assert_eq!(result, Err(Ok(Error::SpendingLimitExceeded)));
+}
+
+#[test]
+fn test_allowances_cleared_with_signer() {
+    let s = setup(3, 2);
+    let grant = |signer: &Address| {
+        let allowance = Allowance {
+            amount: 100,
+            expires_at: s.env.ledger().timestamp() + 30 * 86_400,
+        };
+        Operation::GrantAllowance(signer.clone(), s.token.address.clone(), allowance)
+    };
+    let (first, third) = (s.signers.get(0).unwrap(), s.signers.get(2).unwrap());
+    execute_batch(&s, vec![&s.env, grant(&first), grant(&third)]);
+
+    // A removed signer's allowance does not come back when re-added.
+    s.client.remove_signer(&s.admin, &third);
+    assert_published(
+        &s,
+        AllowanceRevoked {
+            signer: third.clone(),
+            token: s.token.address.clone(),
+        },
+    );
+    s.client.add_signer(&s.admin, &third, &1);
+    assert_eq!(s.client.get_allowance(&third, &s.token.address), None);
+    let result = s
+        .client
+        .try_spend_allowance(&third, &s.token.address, &third, &1);
+    assert_eq!(result, Err(Ok(Error::AllowanceNotFound)));
+
+    // Nor do allowances survive a recovery, even for signers kept on.
+    let guardians = setup_guardians(&s, 1, 1, MIN_RECOVERY_DELAY);
+    s.client
+        .initiate_recovery(&guardians.get(0).unwrap(), &s.signers, &2);
+    s.env
+        .ledger()
+        .with_mut(|l| l.timestamp += MIN_RECOVERY_DELAY);
+    s.client.complete_recovery();
+    assert_eq!(s.client.get_allowance(&first, &s.token.address), None);
+}
+
+#[test]
+fn test_migrate_lists_allowance_tokens() {
+    let s = setup(4, 2);
+    let (second, third) = (s.signers.get(1).unwrap(), s.signers.get(2).unwrap());
+    let allowance = Allowance {
+        amount: 100,
+        expires_at: s.env.ledger().timestamp() + 86_400,
+    };
+    execute_batch(
+        &s,
+        vec![
+            &s.env,
+            Operation::GrantAllowance(second.clone(), s.token.address.clone(), allowance.clone()),
+            Operation::GrantAllowance(third.clone(), s.token.address.clone(), allowance.clone()),
+        ],
+    );
+    s.client.remove_signer(&s.admin, &third);
+
+    // Before version 9, removals left allowances and no token lists.
+    let orphan = DataKey::Allowance(third.clone(), s.token.address.clone());
+    let tokens = DataKey::AllowanceTokens(second.clone());
+    s.env.as_contract(&s.client.address, || {
+        let storage = s.env.storage();
+        storage.persistent().set(&orphan, &allowance);
+        storage.persistent().remove(&tokens);
+        storage.instance().set(&DataKey::SchemaVersion, &8u32);
+    });
+
+    s.client.migrate();
+    s.env.as_contract(&s.client.address, || {
+        let storage = s.env.storage().persistent();
+        assert!(!storage.has(&orphan));
+        let listed: Vec<Address> = storage.get(&tokens).unwrap();
+        assert_eq!(listed, vec![&s.env, s.token.address.clone()]);
+    });
+    s.client.remove_signer(&s.admin, &second);
+    assert_eq!(s.client.get_allowance(&second, &s.token.address), None);
+}
+
+fn execute_batch(s: &Setup, operations: Vec<Operation>) {
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &operations,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    for i in 1..s.signers.len() {
+        let tx = s.client.get_transaction(&tx_id).unwrap();
+        if tx.status != TransactionStatus::Pending {
+            break;
+        }
+        s.client
+            .approve_transaction(&s.signers.get(i).unwrap(), &tx_id);
+    }
+}
+
+#[test]
+fn test_recipient_allowlist() {
+    let s = setup(2, 2);
+    let vetted = Address::generate(&s.env);
+    let unknown = Address::generate(&s.env);
+    let signer = s.signers.get(0).unwrap();
+    let data = Bytes::new(&s.env);
+
+    execute_batch(
+        &s,
+        vec![
+            &s.env,
+            Operation::SetAllowlistEnabled(true),
+            Operation::SetRecipientAllowed(vetted.clone(), true),
+        ],
+    );
+    assert!(s.client.is_allowlist_enabled());
+    assert!(s.client.is_recipient_permitted(&vetted));
+    assert!(!s.client.is_recipient_permitted(&unknown));
+
+    let result =
+        s.client
+            .try_propose_transaction(&signer, &s.token.address, &unknown, &100, &data, &None);
+    assert_eq!(result, Err(Ok(Error::RecipientNotAllowed)));
+
+    // A proposal to a vetted recipient fails if it is delisted before execution.
+    let tx_id =
+        s.client
+            .propose_transaction(&signer, &s.token.address, &vetted, &100, &data, &None);
+    execute_batch(
+        &s,
+        vec![
+            &s.env,
+            Operation::SetRecipientAllowed(vetted.clone(), false),
+        ],
+    );
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::RecipientNotAllowed)));
+}
+
+#[test]
+fn test_recipient_denylist() {
+    let s = setup(2, 2);
+    let sanctioned = Address::generate(&s.env);
+    let signer = s.signers.get(0).unwrap();
+
+    execute_batch(
+        &s,
+        vec![
+            &s.env,
+            Operation::SetRecipientDenied(sanctioned.clone(), true),
+        ],
+    );
+    let result = s.client.try_propose_transaction(
+        &signer,
+        &s.token.address,
+        &sanctioned,
+        &100,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::RecipientDenied)));
+
+    execute_batch(
+        &s,
+        vec![
+            &s.env,
+            Operation::GrantAllowance(
+                signer.clone(),
+                s.token.address.clone(),
+                Allowance {
+                    amount: 100,
+                    expires_at: s.env.ledger().timestamp() + 3_600,
+                },
+            ),
+        ],
+    );
+    let result = s
+        .client
+        .try_spend_allowance(&signer, &s.token.address, &sanctioned, &100);
+    assert_eq!(result, Err(Ok(Error::RecipientDenied)));
+}
+
+#[test]
+fn test_recipient_lists_cover_token_calls() {
+    let s = setup(3, 2);
+    let keys = register_keys(&s);
+    let sanctioned = Address::generate(&s.env);
+    let signer = s.signers.get(0).unwrap();
+    execute_batch(
+        &s,
+        vec![
+            &s.env,
+            Operation::SetRecipientDenied(sanctioned.clone(), true),
+        ],
+    );
+
+    let transfer_args: Vec<Val> = vec![
+        &s.env,
+        s.client.address.into_val(&s.env),
+        sanctioned.into_val(&s.env),
+        100i128.into_val(&s.env),
+    ];
+    let result = s.client.try_propose_invocation(
+        &signer,
+        &s.token.address,
+        &symbol_short!("transfer"),
+        &transfer_args,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::RecipientDenied)));
+
+    let approve_args: Vec<Val> = vec![
+        &s.env,
+        s.client.address.into_val(&s.env),
+        sanctioned.into_val(&s.env),
+        100i128.into_val(&s.env),
+        1_000u32.into_val(&s.env),
+    ];
+    let result = s.client.try_propose_invocation(
+        &signer,
+        &s.token.address,
+        &symbol_short!("approve"),
+        &approve_args,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::RecipientDenied)));
+
+    // A muxed recipient is checked as its underlying account.
+    let muxed = MuxedAddress::generate(&s.env);
+    execute_batch(
+        &s,
+        vec![&s.env, Operation::SetRecipientDenied(muxed.address(), true)],
+    );
+    let muxed_args: Vec<Val> = vec![
+        &s.env,
+        s.client.address.into_val(&s.env),
+        muxed.into_val(&s.env),
+        100i128.into_val(&s.env),
+    ];
+    let result = s.client.try_propose_invocation(
+        &signer,
+        &s.token.address,
+        &symbol_short!("transfer"),
+        &muxed_args,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::RecipientDenied)));
+
+    // A transfer without a readable recipient is refused outright.
+    let malformed_args: Vec<Val> = vec![
+        &s.env,
+        s.client.address.into_val(&s.env),
+        100i128.into_val(&s.env),
+    ];
+    let result = s.client.try_propose_invocation(
+        &signer,
+        &s.token.address,
+        &symbol_short!("transfer"),
+        &malformed_args,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::RecipientNotAllowed)));
+
+    // Signing the token call directly is refused as well.
+    let payload = BytesN::from_array(&s.env, &[9; 32]);
+    let message = Bytes::from(payload.clone());
+    let signatures: Vec<(Address, SignerSignature)> = vec![
+        &s.env,
+        (s.signers.get(0).unwrap(), sign(&s, &keys[0], &message)),
+        (s.signers.get(1).unwrap(), sign(&s, &keys[1], &message)),
+    ];
+    for args in [transfer_args, muxed_args] {
+        let context = Context::Contract(ContractContext {
+            contract: s.token.address.clone(),
+            fn_name: symbol_short!("transfer"),
+            args,
+        });
+        let result = s.env.try_invoke_contract_check_auth::<Error>(
+            &s.client.address,
+            &payload,
+            signatures.clone().into_val(&s.env),
+            &vec![&s.env, context],
+        );
+        assert_eq!(result, Err(Ok(Error::RecipientDenied)));
+    }
+}
+
+fn setup_guardians(s: &Setup, n: u32, threshold: u32, delay: u64) -> Vec<Address> {
+    let mut guardians = Vec::new(&s.env);
+    for _ in 0..n {
+        guardians.push_back(Address::generate(&s.env));
+    }
+    s.client.set_guardians(
+        &s.admin,
+        &GuardianConfig {
+            guardians: guardians.clone(),
+            threshold,
+            delay,
+        },
+    );
+    guardians
+}
+
+#[test]
+fn test_guardian_recovery() {
+    let s = setup(3, 2);
+    let guardians = setup_guardians(&s, 3, 2, 86_400);
+    let new_signers = vec![&s.env, Address::generate(&s.env), Address::generate(&s.env)];
+
+    // Proposals tuned to the old signers are dropped along with them.
+    s.client.set_approval_tiers(
+        &s.admin,
+        &vec![
+            &s.env,
+            ApprovalTier {
+                max_amount: 100,
+                threshold: 3,
+            },
+        ],
+    );
+
+    let result = s
+        .client
+        .try_initiate_recovery(&s.signers.get(0).unwrap(), &new_signers, &2);
+    assert_eq!(result, Err(Ok(Error::NotGuardian)));
+
+    s.client
+        .initiate_recovery(&guardians.get(0).unwrap(), &new_signers, &2);
+    assert_eq!(
+        s.client.try_complete_recovery(),
+        Err(Ok(Error::RecoveryNotReady))
+    );
+
+    s.client.approve_recovery(&guardians.get(1).unwrap());
+    let ready_at = s.env.ledger().timestamp() + 86_400;
+    assert_published(&s, RecoveryScheduled { ready_at });
+    assert_eq!(s.client.get_recovery().unwrap().ready_at, Some(ready_at));
+    assert_eq!(
+        s.client.try_complete_recovery(),
+        Err(Ok(Error::RecoveryNotReady))
+    );
+
+    s.env.ledger().set_timestamp(ready_at);
+    s.client.complete_recovery();
+    assert_published(
+        &s,
+        RecoveryCompleted {
+            signers: new_signers.clone(),
+            threshold: 2,
+        },
+    );
+
+    assert_eq!(s.client.get_signers(), new_signers);
+    assert_eq!(s.client.get_threshold(), 2);
+    assert_eq!(s.client.get_signer_weight(&s.signers.get(0).unwrap()), 0);
+    assert!(s.client.get_approval_tiers().is_empty());
+    assert_eq!(s.client.get_recovery(), None);
+
+    // The new signers can move funds on their own.
+    let to = Address::generate(&s.env);
+    let tx_id = s.client.propose_transaction(
+        &new_signers.get(0).unwrap(),
+        &s.token.address,
+        &to,
+        &100,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client
+        .approve_transaction(&new_signers.get(1).unwrap(), &tx_id);
+    assert_eq!(s.token.balance(&to), 100);
+}
+
+#[test]
+fn test_recovery_veto() {
+    let s = setup(2, 2);
+    let guardians = setup_guardians(&s, 1, 1, 86_400);
+    let guardian = guardians.get(0).unwrap();
+    let new_signers = vec![&s.env, Address::generate(&s.env)];
+
+    s.client.initiate_recovery(&guardian, &new_signers, &1);
+    assert_eq!(
+        s.client.try_initiate_recovery(&guardian, &new_signers, &1),
+        Err(Ok(Error::RecoveryInProgress))
+    );
+
+    let signer = s.signers.get(1).unwrap();
+    s.client.veto_recovery(&signer);
+    assert_published(&s, RecoveryVetoed { signer });
+
+    s.env
+        .ledger()
+        .set_timestamp(s.env.ledger().timestamp() + 86_400);
+    assert_eq!(
+        s.client.try_complete_recovery(),
+        Err(Ok(Error::RecoveryNotFound))
+    );
+    assert_eq!(s.client.get_signers(), s.signers);
+}
+
+#[test]
+fn test_set_guardians_validation() {
+    let s = setup(2, 2);
+    let guardian = Address::generate(&s.env);
+
+    let result = s.client.try_set_guardians(
+        &s.admin,
+        &GuardianConfig {
+            guardians: vec![&s.env, guardian.clone(), guardian.clone()],
+            threshold: 1,
+            delay: 0,
+        },
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidGuardians)));
+
+    let result = s.client.try_set_guardians(
+        &s.admin,
+        &GuardianConfig {
+            guardians: vec![&s.env, guardian.clone()],
+            threshold: 2,
+            delay: 86_400,
+        },
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidGuardians)));
+
+    // Signers need time to notice and veto a recovery.
+    let result = s.client.try_set_guardians(
+        &s.admin,
+        &GuardianConfig {
+            guardians: vec![&s.env, guardian.clone()],
+            threshold: 1,
+            delay: MIN_RECOVERY_DELAY - 1,
+        },
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidGuardians)));
+
+    // Replacing the guardians discards a recovery in progress.
+    setup_guardians(&s, 1, 1, MIN_RECOVERY_DELAY);
+    let guardian = s.client.get_guardians().unwrap().guardians.get(0).unwrap();
+    s.client
+        .initiate_recovery(&guardian, &vec![&s.env, Address::generate(&s.env)], &1);
+    execute_batch(
+        &s,
+        vec![
+            &s.env,
+            Operation::SetGuardians(GuardianConfig {
+                guardians: Vec::new(&s.env),
+                threshold: 0,
+                delay: 0,
+            }),
+        ],
+    );
+    assert_eq!(s.client.get_guardians(), None);
+    assert_eq!(s.client.get_recovery(), None);
+}
+
+#[test]
+fn test_guardian_pause_freezes_outflows() {
+    let s = setup(3, 2);
+    let guardians = setup_guardians(&s, 1, 1, 86_400);
+    let guardian = guardians.get(0).unwrap();
+    let tx_id = propose_payment(&s, 0, 100);
+
+    s.client.pause(&guardian);
+    assert_published(
+        &s,
+        Paused {
+            caller: guardian.clone(),
+        },
+    );
+    assert!(s.client.is_paused());
+
+    let signer = s.signers.get(1).unwrap();
+    assert_eq!(
+        s.client.try_approve_transaction(&signer, &tx_id),
+        Err(Ok(Error::ContractPaused))
+    );
+    assert_eq!(
+        s.client.try_propose_transaction(
+            &signer,
+            &s.token.address,
+            &Address::generate(&s.env),
+            &100,
+            &Bytes::new(&s.env),
+            &None,
+        ),
+        Err(Ok(Error::ContractPaused))
+    );
+    assert_eq!(s.client.try_pause(&signer), Err(Ok(Error::ContractPaused)));
+
+    // Queries keep working while paused.
+    assert_eq!(
+        s.client.get_transaction(&tx_id).unwrap().status,
+        TransactionStatus::Pending
+    );
+
+    // Guardians cannot lift the pause; signers need the full threshold.
+    assert_eq!(s.client.try_unpause(&guardian), Err(Ok(Error::NotSigner)));
+    s.client.unpause(&s.signers.get(0).unwrap());
+    assert!(s.client.is_paused());
+    s.client.unpause(&signer);
+    assert!(!s.client.is_paused());
+
+    s.client.approve_transaction(&signer, &tx_id);
+    assert_eq!(
+        s.client.get_transaction(&tx_id).unwrap().status,
+        TransactionStatus::Executed
+    );
+}
+
+#[test]
+fn test_pause_threshold() {
+    let s = setup(4, 2);
+    s.client.set_pause_threshold(&s.admin, &2);
+    assert_eq!(
+        s.client.try_set_pause_threshold(&s.admin, &0),
+        Err(Ok(Error::InvalidThreshold))
+    );
+    // Only four signers and no guardians could vote.
+    assert_eq!(
+        s.client.try_set_pause_threshold(&s.admin, &5),
+        Err(Ok(Error::InvalidThreshold))
+    );
+    assert_eq!(
+        s.client.try_pause(&Address::generate(&s.env)),
+        Err(Ok(Error::NotSigner))
+    );
+
+    let first = s.signers.get(0).unwrap();
+    s.client.pause(&first);
+    s.client.pause(&first);
+    assert!(!s.client.is_paused());
+    assert_eq!(s.client.get_pause_votes(), vec![&s.env, first.clone()]);
+
+    // A removed signer's vote no longer counts.
+    s.client.remove_signer(&s.admin, &first);
+    let second = s.signers.get(1).unwrap();
+    s.client.pause(&second);
+    assert!(!s.client.is_paused());
+    assert_eq!(s.client.get_pause_votes(), vec![&s.env, second]);
+
+    s.client.pause(&s.signers.get(2).unwrap());
+    assert!(s.client.is_paused());
+    assert!(s.client.get_pause_votes().is_empty());
+
+    // The admin can unpause alone.
+    s.client.unpause(&s.admin);
+    assert!(!s.client.is_paused());
+    assert_eq!(s.client.try_unpause(&s.admin), Err(Ok(Error::NotPaused)));
+}
+
+#[test]
+fn test_signers_remove_a_pauser_without_admin() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let signers = vec![
+        &env,
+        Address::generate(&env),
+        Address::generate(&env),
+        Address::generate(&env),
+    ];
+    let contract_id = env.register(MultiSigContract, ());
+    let client = MultiSigContractClient::new(&env, &contract_id);
+    client.initialize(&None, &signers, &2, &0, &0);
+
+    let rogue = signers.get(0).unwrap();
+    let first = signers.get(1).unwrap();
+    let second = signers.get(2).unwrap();
+    client.pause(&rogue);
+    assert!(client.is_paused());
+
+    // Other proposals stay frozen, even mixed with a removal.
+    let mixed = vec![
+        &env,
+        Operation::RemoveSigner(rogue.clone()),
+        Operation::UpdateThreshold(1),
+    ];
+    assert_eq!(
+        client.try_propose_batch(&first, &mixed, &Bytes::new(&env), &None),
+        Err(Ok(Error::ContractPaused))
+    );
+
+    let tx_id = client.propose_batch(
+        &first,
+        &vec![&env, Operation::RemoveSigner(rogue.clone())],
+        &Bytes::new(&env),
+        &None,
+    );
+    client.approve_transaction(&first, &tx_id);
+    client.approve_transaction(&second, &tx_id);
+    assert_eq!(
+        client.get_transaction(&tx_id).unwrap().status,
+        TransactionStatus::Executed
+    );
+    assert!(!client.get_signers().contains(&rogue));
+
+    client.unpause(&first);
+    client.unpause(&second);
+    assert!(!client.is_paused());
+    assert_eq!(client.try_pause(&rogue), Err(Ok(Error::NotSigner)));
+}
+
+#[test]
+fn test_upgrade_requires_uploaded_wasm() {
+    let s = setup(2, 2);
+    let wasm_hash = BytesN::from_array(&s.env, &[7; 32]);
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &vec![&s.env, Operation::Upgrade(wasm_hash)],
+        &Bytes::new(&s.env),
+        &None,
+    );
+
+    // Unknown code cannot be installed; the approval reverts with it.
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert!(result.is_err());
+    assert_eq!(
+        s.client.get_transaction(&tx_id).unwrap().status,
+        TransactionStatus::Pending
+    );
+
+    // The smallest module the host accepts: only the `contractenvmetav0`
+    // section, declaring interface version 23.
+    let mut wasm = Bytes::from_slice(&s.env, b"\0asm\x01\0\0\0\0\x1e\x11contractenvmetav0");
+    wasm.extend_from_array(&[0, 0, 0, 0, 0, 0, 0, 23, 0, 0, 0, 0]);
+    let wasm_hash = s.env.deployer().upload_contract_wasm(wasm);
+    let tx_id = s.client.propose_batch(
+        &s.signers.get(0).unwrap(),
+        &vec![&s.env, Operation::Upgrade(wasm_hash.clone())],
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_published(&s, Upgraded { wasm_hash });
+}
+
+#[test]
+fn test_migrate() {
+    let s = setup(2, 2);
+    assert_eq!(s.client.get_schema_version(), SCHEMA_VERSION);
+    let tx_id = propose_payment(&s, 0, 100);
+
+    // Vaults deployed before versioning have no version key.
+    s.env.as_contract(&s.client.address, || {
+        s.env.storage().instance().remove(&DataKey::SchemaVersion);
+    });
+    assert_eq!(s.client.get_schema_version(), 0);
+    let signer = s.signers.get(0).unwrap();
+    let result = s.client.try_propose_transaction(
+        &signer,
+        &s.token.address,
+        &Address::generate(&s.env),
+        &100,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::UnknownSchemaVersion)));
+    assert_eq!(
+        s.client.try_pause(&signer),
+        Err(Ok(Error::UnknownSchemaVersion))
+    );
+    assert_eq!(
+        s.client.try_cancel_transaction(&signer, &tx_id),
+        Err(Ok(Error::UnknownSchemaVersion))
+    );
+
+    s.client.migrate();
+    assert_published(
+        &s,
+        Migrated {
+            from: 0,
+            to: SCHEMA_VERSION,
+        },
+    );
+    assert_eq!(s.client.get_schema_version(), SCHEMA_VERSION);
+    s.client.migrate();
+
+    s.env.as_contract(&s.client.address, || {
+        s.env
+            .storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &(SCHEMA_VERSION + 1));
+    });
+    assert_eq!(s.client.try_migrate(), Err(Ok(Error::UnknownSchemaVersion)));
+}
+
+#[test]
+fn test_ttl_extension_and_bump() {
+    let s = setup(2, 2);
+    let tx_id = propose_payment(&s, 0, 100);
+    let key = DataKey::Transaction(tx_id);
+
+    s.env.as_contract(&s.client.address, || {
+        assert_eq!(s.env.storage().persistent().get_ttl(&key), TTL_EXTEND_TO);
+        assert_eq!(s.env.storage().instance().get_ttl(), TTL_EXTEND_TO);
+    });
+
+    s.env
+        .ledger()
+        .with_mut(|ledger| ledger.sequence_number += 10 * 17_280);
+    s.client.bump(&vec![&s.env, key.clone()]);
+    s.env.as_contract(&s.client.address, || {
+        assert_eq!(s.env.storage().persistent().get_ttl(&key), TTL_EXTEND_TO);
+        assert_eq!(
+            s.env
+                .storage()
+                .persistent()
+                .get_ttl(&DataKey::Signer(s.signers.get(1).unwrap())),
+            TTL_EXTEND_TO
+        );
+        assert_eq!(s.env.storage().instance().get_ttl(), TTL_EXTEND_TO);
+    });
+}
+
+#[test]
+fn test_ttl_extended_on_use() {
+    let s = setup(2, 2);
+    let signer = s.signers.get(1).unwrap();
+    let vetted = Address::generate(&s.env);
+    let team = symbol_short!("team");
+    s.client.set_group(&s.admin, &team, &s.signers);
+    s.client.set_group_quorums(
+        &s.admin,
+        &vec![
+            &s.env,
+            GroupQuorum {
+                group: team.clone(),
+                min_approvals: 2,
+            },
+        ],
+    );
+    s.client.set_spending_limit(
+        &s.admin,
+        &s.token.address,
+        &SpendingLimit {
+            amount: 500,
+            window: 86_400,
+        },
+    );
+    let guardians = setup_guardians(&s, 2, 2, MIN_RECOVERY_DELAY);
+    let allowance = Allowance {
+        amount: 100,
+        expires_at: 365 * 86_400,
+    };
+    execute_batch(
+        &s,
+        vec![
+            &s.env,
+            Operation::SetRecipientAllowed(vetted.clone(), true),
+            Operation::SetAllowlistEnabled(true),
+            Operation::GrantAllowance(signer.clone(), s.token.address.clone(), allowance),
+        ],
+    );
+
+    s.env
+        .ledger()
+        .with_mut(|ledger| ledger.sequence_number += 10 * 17_280);
+    let tx_id = s.client.propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &vetted,
+        &10,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    s.client.approve_transaction(&signer, &tx_id);
+    s.client
+        .spend_allowance(&signer, &s.token.address, &vetted, &10);
+    s.client
+        .initiate_recovery(&guardians.get(0).unwrap(), &s.signers, &1);
+
+    s.env.as_contract(&s.client.address, || {
+        for key in [
+            DataKey::Groups,
+            DataKey::Group(team),
+            DataKey::SpendingLimit(s.token.address.clone()),
+            DataKey::SpendingHistory(s.token.address.clone()),
+            DataKey::Allowance(signer.clone(), s.token.address.clone()),
+            DataKey::AllowedRecipient(vetted.clone()),
+            DataKey::Guardians,
+            DataKey::Recovery,
+        ] {
+            assert_eq!(s.env.storage().persistent().get_ttl(&key), TTL_EXTEND_TO);
+        }
+    });
+}
+
+#[test]
+fn test_migrate_moves_settings_to_instance() {
+    let s = setup(2, 2);
+    let tx_id = propose_payment(&s, 0, 100);
+
+    // Recreate the version 1 layout, which kept these in persistent storage.
+    s.env.as_contract(&s.client.address, || {
+        let storage = s.env.storage();
+        for key in [DataKey::Admin, DataKey::Threshold, DataKey::NextId] {
+            let value: Val = storage.instance().get(&key).unwrap();
+            storage.persistent().set(&key, &value);
+            storage.instance().remove(&key);
+        }
+        storage.instance().remove(&DataKey::SchemaVersion);
+        storage.persistent().set(&DataKey::SchemaVersion, &1u32);
+    });
+    assert_eq!(s.client.get_threshold(), 0);
+    assert_eq!(
+        s.client
+            .try_initialize(&None, &vec![&s.env, Address::generate(&s.env)], &1, &0, &0),
+        Err(Ok(Error::AlreadyInitialized))
+    );
+
+    s.client.migrate();
+    assert_eq!(s.client.get_schema_version(), SCHEMA_VERSION);
+    assert_eq!(s.client.get_threshold(), 2);
+    assert_eq!(s.client.get_admin(), Some(s.admin.clone()));
+    assert_eq!(propose_payment(&s, 0, 100), tx_id + 1);
+    s.env.as_contract(&s.client.address, || {
+        assert!(!s.env.storage().persistent().has(&DataKey::Threshold));
+    });
+}
+
+#[test]
+fn test_list_transactions() {
+    let s = setup(2, 2);
+    let ids: Vec<u64> = vec![
+        &s.env,
+        propose_payment(&s, 0, 10),
+        propose_payment(&s, 0, 20),
+        propose_payment(&s, 0, 30),
+        propose_payment(&s, 0, 40),
+    ];
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &ids.get(1).unwrap());
+    s.client
+        .cancel_transaction(&s.signers.get(0).unwrap(), &ids.get(2).unwrap());
+
+    let page = s
+        .client
+        .list_transactions(&0, &10, &Some(TransactionStatus::Pending));
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0).unwrap().id, ids.get(0).unwrap());
+    assert_eq!(page.get(1).unwrap().id, ids.get(3).unwrap());
+
+    let executed = s
+        .client
+        .list_transactions(&0, &10, &Some(TransactionStatus::Executed));
+    assert_eq!(executed.len(), 1);
+    assert_eq!(executed.get(0).unwrap().id, ids.get(1).unwrap());
+
+    // Pages continue from one past the last id returned.
+    let first = s.client.list_transactions(&0, &3, &None);
+    assert_eq!(first.len(), 3);
+    let next = first.get(2).unwrap().id + 1;
+    let second = s.client.list_transactions(&next, &3, &None);
+    assert_eq!(second.len(), 1);
+    assert_eq!(second.get(0).unwrap().id, ids.get(3).unwrap());
+
+    let page = s.client.list_transactions(
+        &(ids.get(0).unwrap() + 1),
+        &1,
+        &Some(TransactionStatus::Pending),
+    );
+    assert_eq!(page.get(0).unwrap().id, ids.get(3).unwrap());
+}
+
+#[test]
+fn test_migrate_indexes_transactions() {
+    let s = setup(2, 2);
+    let pending = propose_payment(&s, 0, 10);
+    let cancelled = propose_payment(&s, 0, 20);
+    s.client
+        .cancel_transaction(&s.signers.get(0).unwrap(), &cancelled);
+    for _ in 0..MIGRATION_BATCH {
+        propose_payment(&s, 0, 1);
+    }
+
+    s.env.as_contract(&s.client.address, || {
+        let storage = s.env.storage().persistent();
+        storage.remove(&DataKey::StatusIndex(TransactionStatus::Pending));
+        storage.set(&DataKey::SchemaVersion, &2u32);
+        s.env.storage().instance().remove(&DataKey::SchemaVersion);
+    });
+    assert!(s
+        .client
+        .list_transactions(&0, &10, &Some(TransactionStatus::Pending))
+        .is_empty());
+
+    // The first call indexes one batch and stops there.
+    s.client.migrate();
+    assert_eq!(s.client.get_schema_version(), 2);
+    let page = s
+        .client
+        .list_transactions(&0, &MAX_PAGE_SIZE, &Some(TransactionStatus::Pending));
+    assert_eq!(page.len() as u64, MIGRATION_BATCH - 1);
+    assert_eq!(page.get(0).unwrap().id, pending);
+
+    // Later steps visit every transaction again, a batch per call.
+    s.client.migrate();
+    assert_eq!(s.client.get_schema_version(), 7);
+    s.client.migrate();
+    assert_eq!(s.client.get_schema_version(), 8);
+    s.client.migrate();
+    assert_eq!(s.client.get_schema_version(), SCHEMA_VERSION);
+    let page = s
+        .client
+        .list_transactions(&0, &MAX_PAGE_SIZE, &Some(TransactionStatus::Pending));
+    assert_eq!(page.len() as u64, MIGRATION_BATCH + 1);
+    let page = s
+        .client
+        .list_transactions(&0, &10, &Some(TransactionStatus::Cancelled));
+    assert_eq!(page.get(0).unwrap().id, cancelled);
+}
+
+#[test]
+fn test_migrate_drops_closed_status_indexes() {
+    let s = setup(2, 2);
+    let tx_id = propose_payment(&s, 0, 10);
+    s.client
+        .approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    let key = DataKey::StatusIndex(TransactionStatus::Executed);
+
+    s.env.as_contract(&s.client.address, || {
+        assert!(!s.env.storage().persistent().has(&key));
+        s.env.storage().persistent().set(&key, &vec![&s.env, tx_id]);
+        s.env
+            .storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &6u32);
+    });
+
+    s.client.migrate();
+    s.env.as_contract(&s.client.address, || {
+        assert!(!s.env.storage().persistent().has(&key));
+    });
+    let page = s
+        .client
+        .list_transactions(&0, &10, &Some(TransactionStatus::Executed));
+    assert_eq!(page.get(0).unwrap().id, tx_id);
+}
+
+#[test]
+fn test_migrate_carries_spending_windows() {
+    let s = setup(2, 2);
+    let limit = SpendingLimit {
+        amount: 300,
+        window: 86_400,
+    };
+    s.client
+        .set_spending_limit(&s.admin, &s.token.address, &limit);
+    let tx_id = propose_payment(&s, 0, 100);
+    s.env.ledger().with_mut(|l| l.timestamp += 7_200);
+
+    let key = DataKey::SpendingWindow(s.token.address.clone());
+    s.env.as_contract(&s.client.address, || {
+        let window = SpendingWindow {
+            start: 3_600,
+            spent: 250,
+        };
+        s.env.storage().persistent().set(&key, &window);
+        s.env
+            .storage()
+            .instance()
+            .set(&DataKey::SchemaVersion, &7u32);
+    });
+
+    s.client.migrate();
+    assert_eq!(s.client.get_schema_version(), SCHEMA_VERSION);
+    s.env.as_contract(&s.client.address, || {
+        assert!(!s.env.storage().persistent().has(&key));
+    });
+    let bucket = s.env.ledger().timestamp() / 3_600;
+    assert_eq!(
+        s.client.get_spending_history(&s.token.address),
+        Some(SpendingHistory {
+            buckets: map![&s.env, (bucket, 250)],
+        })
+    );
+
+    // What the old window spent still counts against the limit.
+    let result = s
+        .client
+        .try_approve_transaction(&s.signers.get(1).unwrap(), &tx_id);
+    assert_eq!(result, Err(Ok(Error::SpendingLimitExceeded)));
+}
+
+#[test]
+fn test_signer_inbox() {
+    let s = setup(4, 3);
+    let (first, second, third) = (
+        s.signers.get(0).unwrap(),
+        s.signers.get(1).unwrap(),
+        s.signers.get(2).unwrap(),
+    );
+    let tx_a = propose_payment(&s, 0, 10);
+    let tx_b = propose_payment(&s, 0, 20);
+
+    assert!(s.client.get_inbox(&first).is_empty());
+    assert_eq!(s.client.get_inbox(&second), vec![&s.env, tx_a, tx_b]);
+
+    s.client.approve_transaction(&second, &tx_a);
+    s.client.reject_transaction(&third, &tx_b);
+    assert_eq!(s.client.get_inbox(&second), vec![&s.env, tx_b]);
+    assert_eq!(s.client.get_inbox(&third), vec![&s.env, tx_a]);
+
+    s.client.revoke_approval(&second, &tx_a);
+    assert_eq!(s.client.get_inbox(&second), vec![&s.env, tx_a, tx_b]);
+
+    // New signers pick up the proposals still awaiting votes.
+    let newcomer = Address::generate(&s.env);
+    s.client.add_signer(&s.admin, &newcomer, &1);
+    assert_eq!(s.client.get_inbox(&newcomer), vec![&s.env, tx_a, tx_b]);
+    s.client.remove_signer(&s.admin, &newcomer);
+    assert!(s.client.get_inbox(&newcomer).is_empty());
+
+    // Proposals leave every inbox once they stop pending.
+    s.client.cancel_transaction(&first, &tx_b);
+    assert_eq!(s.client.get_inbox(&second), vec![&s.env, tx_a]);
+    s.client.approve_transaction(&second, &tx_a);
+    s.client.approve_transaction(&third, &tx_a);
+    assert!(s.client.get_inbox(&second).is_empty());
+    assert!(s.client.get_inbox(&third).is_empty());
+}
+
+#[test]
+fn test_migrate_fills_inboxes() {
+    let s = setup(2, 2);
+    let tx_id = propose_payment(&s, 0, 10);
+    let second = s.signers.get(1).unwrap();
+
+    s.env.as_contract(&s.client.address, || {
+        let storage = s.env.storage().persistent();
+        storage.remove(&DataKey::Inbox(second.clone()));
+        storage.set(&DataKey::SchemaVersion, &3u32);
+        s.env.storage().instance().remove(&DataKey::SchemaVersion);
+    });
+    assert!(s.client.get_inbox(&second).is_empty());
+
+    s.client.migrate();
+    assert_eq!(s.client.get_inbox(&second), vec![&s.env, tx_id]);
+}
+
+fn sign(s: &Setup, key: &SigningKey, message: &Bytes) -> SignerSignature {
+    let message: std::vec::Vec<u8> = message.iter().collect();
+    SignerSignature::Ed25519(BytesN::from_array(&s.env, &key.sign(&message).to_bytes()))
+}
+
+fn base64url(bytes: &[u8]) -> std::string::String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut encoded = std::string::String::new();
+    for chunk in bytes.chunks(3) {
+        let mut group = 0u32;
+        for (i, byte) in chunk.iter().enumerate() {
+            group |= (*byte as u32) << (16 - 8 * i);
+        }
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(group >> (18 - 6 * i)) as usize & 63] as char);
+        }
+    }
+    encoded
+}
+
+/// A WebAuthn assertion by `key` with the given client data and flags.
+fn webauthn(
+    s: &Setup,
+    key: &p256::ecdsa::SigningKey,
+    client_data_json: &str,
+    flags: u8,
+) -> SignerSignature {
+    let mut authenticator_data = Bytes::from_array(&s.env, &[7; 32]);
+    authenticator_data.extend_from_array(&[flags, 0, 0, 0, 1]);
+    let client_data_json = Bytes::from_slice(&s.env, client_data_json.as_bytes());
+    let mut message = authenticator_data.clone();
+    message.append(&s.env.crypto().sha256(&client_data_json).into());
+    let digest = s.env.crypto().sha256(&message).to_array();
+
+    let signature: P256Signature = key.sign_prehash(&digest).unwrap();
+    let signature = signature.normalize_s().unwrap_or(signature);
+    SignerSignature::Secp256r1(WebAuthnSignature {
+        authenticator_data,
+        client_data_json,
+        signature: BytesN::from_array(&s.env, &signature.to_bytes().into()),
+    })
+}
+
+/// Client data of a WebAuthn assertion over `digest`.
+fn client_data(digest: &Bytes) -> std::string::String {
+    let digest: std::vec::Vec<u8> = digest.iter().collect();
+    std::format!(
+        r#"{{"type":"webauthn.get","challenge":"{}","origin":"https://vault.example","crossOrigin":false}}"#,
+        base64url(&digest)
+    )
+}
+
+fn sign_p256(s: &Setup, key: &p256::ecdsa::SigningKey, digest: &Bytes) -> SignerSignature {
+    webauthn(s, key, &client_data(digest), 0x05)
+}
+
+/// Digest a signer signs to prove it holds the key it registers.
+fn key_digest(s: &Setup, signer: &Address) -> Bytes {
+    let preimage = (
+        Symbol::new(&s.env, "signer_key"),
+        s.client.address.clone(),
+        signer.clone(),
+    )
+        .to_xdr(&s.env);
+    s.env.crypto().sha256(&preimage).into()
+}
+
+/// Registers a deterministic ed25519 key for each signer.
+fn register_keys(s: &Setup) -> std::vec::Vec<SigningKey> {
+    let mut keys = std::vec::Vec::new();
+    for (i, signer) in s.signers.iter().enumerate() {
+        let key = SigningKey::from_bytes(&[i as u8 + 1; 32]);
+        let public_key = BytesN::from_array(&s.env, &key.verifying_key().to_bytes());
+        s.client.set_signer_key(
+            &signer,
+            &SignerKey::Ed25519(public_key),
+            &sign(s, &key, &key_digest(s, &signer)),
+        );
+        keys.push(key);
+    }
+    keys
+}
+
+/// Replaces a signer's key with a deterministic passkey.
+fn register_passkey(s: &Setup, signer: &Address) -> p256::ecdsa::SigningKey {
+    let key = p256::ecdsa::SigningKey::from_slice(&[42; 32]).unwrap();
+    let point = key.verifying_key().to_encoded_point(false);
+    s.client.set_signer_key(
+        signer,
+        &SignerKey::Secp256r1(BytesN::from_array(
+            &s.env,
+            point.as_bytes().try_into().unwrap(),
+        )),
+        &sign_p256(s, &key, &key_digest(s, signer)),
+    );
+    key
+}
+
+fn payment_payload(s: &Setup, to: &Address, amount: i128) -> ProposalPayload {
+    ProposalPayload {
+        nonce: 0,
+        valid_until: s.env.ledger().timestamp() + 3_600,
+        operations: vec![&s.env, transfer(s, to, amount)],
+        data: Bytes::new(&s.env),
+    }
+}
+
+#[test]
+fn test_execute_with_signatures() {
+    let s = setup(3, 2);
+    let keys = register_keys(&s);
+    let to = Address::generate(&s.env);
+    let payload = payment_payload(&s, &to, 100);
+    let hash = Bytes::from(s.client.proposal_hash(&payload));
+
+    let signatures = vec![
+        &s.env,
+        (s.signers.get(0).unwrap(), sign(&s, &keys[0], &hash)),
+        (s.signers.get(2).unwrap(), sign(&s, &keys[2], &hash)),
+    ];
+    // Proposals made on-chain meanwhile do not invalidate the bundle.
+    propose_payment(&s, 1, 10);
+    let tx_id = s.client.execute_with_signatures(&payload, &signatures);
+
+    assert_eq!(s.token.balance(&to), 100);
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    assert_eq!(tx.status, TransactionStatus::Executed);
+    assert_eq!(tx.proposed_by, s.signers.get(0).unwrap());
+
+    // The same signatures cannot be replayed; a new nonce needs new ones.
+    assert_eq!(
+        s.client.try_execute_with_signatures(&payload, &signatures),
+        Err(Ok(Error::SignatureReplayed))
+    );
+    let payload = ProposalPayload {
+        nonce: 1,
+        ..payload
+    };
+    assert!(s
+        .client
+        .try_execute_with_signatures(&payload, &signatures)
+        .is_err());
+    assert_eq!(s.token.balance(&to), 100);
+}
+
+#[test]
+fn test_execute_with_signatures_errors() {
+    let s = setup(3, 2);
+    let keys = register_keys(&s);
+    let to = Address::generate(&s.env);
+    let payload = payment_payload(&s, &to, 100);
+    let hash = Bytes::from(s.client.proposal_hash(&payload));
+    let first = (s.signers.get(0).unwrap(), sign(&s, &keys[0], &hash));
+
+    // A signer repeated does not add weight.
+    let result = s
+        .client
+        .try_execute_with_signatures(&payload, &vec![&s.env, first.clone(), first.clone()]);
+    assert_eq!(result, Err(Ok(Error::InsufficientApprovals)));
+
+    // A signature by the wrong key traps.
+    let forged = (s.signers.get(1).unwrap(), sign(&s, &keys[0], &hash));
+    let result = s
+        .client
+        .try_execute_with_signatures(&payload, &vec![&s.env, first.clone(), forged]);
+    assert!(result.is_err());
+
+    let outsider = (Address::generate(&s.env), sign(&s, &keys[1], &hash));
+    let result = s
+        .client
+        .try_execute_with_signatures(&payload, &vec![&s.env, first.clone(), outsider]);
+    assert_eq!(result, Err(Ok(Error::NotSigner)));
+    assert_eq!(s.token.balance(&to), 0);
+
+    // A signer can burn a payload before it is submitted.
+    let second = (s.signers.get(1).unwrap(), sign(&s, &keys[1], &hash));
+    let signatures = vec![&s.env, first, second];
+    let revoked = s.client.proposal_hash(&payload);
+    s.client
+        .revoke_payload(&s.signers.get(2).unwrap(), &revoked);
+    assert_published(
+        &s,
+        PayloadRevoked {
+            hash: revoked.clone(),
+            signer: s.signers.get(2).unwrap(),
+        },
+    );
+    assert_eq!(
+        s.client
+            .try_revoke_payload(&s.signers.get(1).unwrap(), &revoked),
+        Err(Ok(Error::SignatureReplayed))
+    );
+    assert_eq!(
+        s.client
+            .try_revoke_payload(&Address::generate(&s.env), &revoked),
+        Err(Ok(Error::NotSigner))
+    );
+    assert_eq!(
+        s.client.try_execute_with_signatures(&payload, &signatures),
+        Err(Ok(Error::SignatureReplayed))
+    );
+
+    // Nor does a payload run past its deadline.
+    let payload = ProposalPayload {
+        nonce: 1,
+        ..payload
+    };
+    let hash = Bytes::from(s.client.proposal_hash(&payload));
+    let signatures = vec![
+        &s.env,
+        (s.signers.get(0).unwrap(), sign(&s, &keys[0], &hash)),
+        (s.signers.get(1).unwrap(), sign(&s, &keys[1], &hash)),
+    ];
+    s.env
+        .ledger()
+        .with_mut(|l| l.timestamp = payload.valid_until + 1);
+    assert_eq!(
+        s.client.try_execute_with_signatures(&payload, &signatures),
+        Err(Ok(Error::TransactionExpired))
+    );
+    assert_eq!(s.token.balance(&to), 0);
+
+    // Removed signers lose their key.
+    s.client.remove_signer(&s.admin, &s.signers.get(2).unwrap());
+    assert_eq!(s.client.get_signer_key(&s.signers.get(2).unwrap()), None);
+}
+
+fn check_auth(
+    s: &Setup,
+    payload: &BytesN<32>,
+    signatures: Vec<(Address, SignerSignature)>,
+    contract: &Address,
+) -> Result<(), Result<Error, soroban_sdk::InvokeError>> {
+    let context = Context::Contract(ContractContext {
+        contract: contract.clone(),
+        fn_name: symbol_short!("set_value"),
+        args: vec![&s.env],
+    });
+    s.env.try_invoke_contract_check_auth::<Error>(
+        &s.client.address,
+        payload,
+        signatures.into_val(&s.env),
+        &vec![&s.env, context],
+    )
+}
+
+#[test]
+fn test_check_auth() {
+    let s = setup(3, 2);
+    let keys = register_keys(&s);
+    let governed = s.env.register(Governed, ());
+    let payload = BytesN::from_array(&s.env, &[9; 32]);
+    let message = Bytes::from(payload.clone());
+    let first = (s.signers.get(0).unwrap(), sign(&s, &keys[0], &message));
+    let second = (s.signers.get(1).unwrap(), sign(&s, &keys[1], &message));
+
+    assert_eq!(
+        check_auth(
+            &s,
+            &payload,
+            vec![&s.env, first.clone(), second.clone()],
+            &governed
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        check_auth(
+            &s,
+            &payload,
+            vec![&s.env, first.clone(), first.clone()],
+            &governed
+        ),
+        Err(Ok(Error::InsufficientApprovals))
+    );
+
+    let signatures = vec![&s.env, first, second];
+    assert_eq!(
+        check_auth(&s, &payload, signatures.clone(), &s.client.address),
+        Err(Ok(Error::SelfInvocation))
+    );
+    s.client.set_spending_limit(
+        &s.admin,
+        &s.token.address,
+        &SpendingLimit {
+            amount: 100,
+            window: 3_600,
+        },
+    );
+    assert_eq!(
+        check_auth(&s, &payload, signatures.clone(), &s.token.address),
+        Err(Ok(Error::LimitedTokenInvocation))
+    );
+
+    s.client.pause(&s.signers.get(2).unwrap());
+    assert_eq!(
+        check_auth(&s, &payload, signatures, &governed),
+        Err(Ok(Error::ContractPaused))
+    );
+}
+
+#[test]
+fn test_check_auth_refused_with_execution_delay() {
+    let s = setup_with_delay(2, 2, 3_600);
+    let keys = register_keys(&s);
+    let governed = s.env.register(Governed, ());
+    let payload = BytesN::from_array(&s.env, &[9; 32]);
+    let message = Bytes::from(payload.clone());
+    let signatures = vec![
+        &s.env,
+        (s.signers.get(0).unwrap(), sign(&s, &keys[0], &message)),
+        (s.signers.get(1).unwrap(), sign(&s, &keys[1], &message)),
+    ];
+
+    assert_eq!(
+        check_auth(&s, &payload, signatures, &governed),
+        Err(Ok(Error::ExecutionDelayActive))
+    );
+}
+
+#[test]
+fn test_passkey_signers() {
+    let s = setup(3, 3);
+    let keys = register_keys(&s);
+    let third = s.signers.get(2).unwrap();
+    let passkey = register_passkey(&s, &third);
+    assert!(matches!(
+        s.client.get_signer_key(&third),
+        Some(SignerKey::Secp256r1(_))
+    ));
+
+    // A relayer submits the passkey approval of an on-chain proposal.
+    let to = Address::generate(&s.env);
+    let tx_id = s.client.propose_transaction(
+        &s.signers.get(0).unwrap(),
+        &s.token.address,
+        &to,
+        &100,
+        &Bytes::new(&s.env),
+        &None,
+    );
+    let hash = Bytes::from(s.client.approval_hash(&tx_id, &third));
+    s.client
+        .approve_with_signature(&third, &tx_id, &sign_p256(&s, &passkey, &hash));
+    assert_eq!(s.client.get_approvals(&tx_id).len(), 2);
+    assert!(s.client.get_inbox(&third).is_empty());
+
+    // Passkey and ed25519 signatures aggregate in one call.
+    let payload = payment_payload(&s, &to, 50);
+    let hash = Bytes::from(s.client.proposal_hash(&payload));
+    s.client.execute_with_signatures(
+        &payload,
+        &vec![
+            &s.env,
+            (s.signers.get(0).unwrap(), sign(&s, &keys[0], &hash)),
+            (s.signers.get(1).unwrap(), sign(&s, &keys[1], &hash)),
+            (third.clone(), sign_p256(&s, &passkey, &hash)),
+        ],
+    );
+    assert_eq!(s.token.balance(&to), 50);
+
+    // A signature over another proposal is rejected.
+    let result =
+        s.client
+            .try_approve_with_signature(&third, &tx_id, &sign_p256(&s, &passkey, &hash));
+    assert!(result.is_err());
+
+    // Approval signatures cannot pass for a payload to execute.
+    let approval = Bytes::from(s.client.approval_hash(&tx_id, &third));
+    let tx = s.client.get_transaction(&tx_id).unwrap();
+    let payload = ProposalPayload {
+        nonce: tx_id,
+        valid_until: u64::MAX,
+        operations: tx.operations,
+        data: tx.data,
+    };
+    let result = s.client.try_execute_with_signatures(
+        &payload,
+        &vec![
+            &s.env,
+            (s.signers.get(0).unwrap(), sign(&s, &keys[0], &approval)),
+            (s.signers.get(1).unwrap(), sign(&s, &keys[1], &approval)),
+            (third.clone(), sign_p256(&s, &passkey, &approval)),
+        ],
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_passkey_assertions_checked() {
+    let s = setup(3, 3);
+    let keys = register_keys(&s);
+    let third = s.signers.get(2).unwrap();
+    let passkey = register_passkey(&s, &third);
+    let tx_id = propose_payment(&s, 0, 100);
+    let hash = Bytes::from(s.client.approval_hash(&tx_id, &third));
+
+    // The client data must be an assertion whose challenge is the hash.
+    let other = Bytes::from_array(&s.env, &[3; 32]);
+    let wrong_challenge = webauthn(&s, &passkey, &client_data(&other), 0x05);
+    let registration = webauthn(
+        &s,
+        &passkey,
+        &client_data(&hash).replace("webauthn.get", "webauthn.create"),
+        0x05,
+    );
+    let absent_user = webauthn(&s, &passkey, &client_data(&hash), 0x04);
+    let wrong_kind = sign(&s, &keys[2], &hash);
+    for signature in [wrong_challenge, registration, absent_user, wrong_kind] {
+        let result = s
+            .client
+            .try_approve_with_signature(&third, &tx_id, &signature);
+        assert_eq!(result, Err(Ok(Error::InvalidSignature)));
+    }
+
+    s.client
+        .approve_with_signature(&third, &tx_id, &sign_p256(&s, &passkey, &hash));
+    assert_eq!(s.client.get_approvals(&tx_id).len(), 2);
+}
+
+#[test]
+fn test_approval_signature_cannot_be_replayed() {
+    let s = setup(4, 3);
+    register_keys(&s);
+    let third = s.signers.get(2).unwrap();
+    let passkey = register_passkey(&s, &third);
+    let tx_id = propose_payment(&s, 0, 100);
+
+    let hash = Bytes::from(s.client.approval_hash(&tx_id, &third));
+    let signature = sign_p256(&s, &passkey, &hash);
+    s.client.approve_with_signature(&third, &tx_id, &signature);
+
+    // Revoking spends the signature; a relayer cannot restore the approval.
+    s.client.revoke_approval(&third, &tx_id);
+    let result = s
+        .client
+        .try_approve_with_signature(&third, &tx_id, &signature);
+    assert!(result.is_err());
+    assert!(!s.client.get_approvals(&tx_id).contains(&third));
+
+    // Nor can an unused signature turn a later rejection into an approval.
+    let hash = Bytes::from(s.client.approval_hash(&tx_id, &third));
+    let signature = sign_p256(&s, &passkey, &hash);
+    s.client.reject_transaction(&third, &tx_id);
+    let result = s
+        .client
+        .try_approve_with_signature(&third, &tx_id, &signature);
+    assert!(result.is_err());
+    assert_eq!(s.client.get_rejections(&tx_id), vec![&s.env, third]);
+    assert_eq!(
+        s.client.get_transaction(&tx_id).unwrap().status,
+        TransactionStatus::Pending
+    );
+}
+
+#[test]
+fn test_migrate_moves_remaining_settings_to_instance() {
+    let s = setup_with_delay(2, 2, 3_600);
+    s.client.set_pause_threshold(&s.admin, &2);
+
+    // Recreate the version 5 layout, which kept these in persistent storage.
+    s.env.as_contract(&s.client.address, || {
+        let storage = s.env.storage();
+        for key in [DataKey::ExecutionDelay, DataKey::PauseThreshold] {
+            let value: Val = storage.instance().get(&key).unwrap();
+            storage.persistent().set(&key, &value);
+            storage.instance().remove(&key);
+        }
+        storage.instance().remove(&DataKey::SchemaVersion);
+        storage.persistent().set(&DataKey::SchemaVersion, &5u32);
+    });
+    assert_eq!(s.client.get_schema_version(), 5);
+
+    s.client.migrate();
+    assert_eq!(s.client.get_schema_version(), SCHEMA_VERSION);
+    assert_eq!(s.client.get_pause_threshold(), 2);
+    s.env.as_contract(&s.client.address, || {
+        let storage = s.env.storage();
+        for key in [
+            DataKey::ExecutionDelay,
+            DataKey::PauseThreshold,
+            DataKey::SchemaVersion,
+        ] {
+            assert!(storage.instance().has(&key));
+            assert!(!storage.persistent().has(&key));
+        }
+    });
+}
+
+#[test]
+fn test_migrate_tags_signer_keys() {
+    let s = setup(2, 2);
+    let signer = s.signers.get(0).unwrap();
+    let public_key = BytesN::from_array(&s.env, &[5; 32]);
+
+    s.env.as_contract(&s.client.address, || {
+        let storage = s.env.storage().persistent();
+        storage.set(&DataKey::SignerKey(signer.clone()), &public_key);
+        storage.set(&DataKey::SchemaVersion, &4u32);
+        s.env.storage().instance().remove(&DataKey::SchemaVersion);
+    });
+
+    s.client.migrate();
+    assert_eq!(
+        s.client.get_signer_key(&signer),
+        Some(SignerKey::Ed25519(public_key))
+    );
 }